@@ -0,0 +1,328 @@
+use std::{
+    io::Cursor,
+    num::{NonZeroU8, NonZeroU32},
+    path::Path,
+};
+
+use anyhow::{Context, bail};
+use rubato::{
+    Async, FixedAsync, Resampler, SincInterpolationParameters, SincInterpolationType,
+    WindowFunction, audioadapter::Adapter, audioadapter_buffers::direct::SequentialSliceOfVecs,
+};
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisDecoder, VorbisEncoderBuilder};
+
+use crate::config::{EncoderBackend, EncodingConfig, Normalise, ProcessingConfig, Trim};
+
+/// Decoded audio, one `Vec` of samples in [-1, 1] per channel
+#[derive(Debug, Clone)]
+pub struct Pcm {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl Pcm {
+    pub fn frames(&self) -> usize {
+        self.channels.first().map(Vec::len).unwrap_or_default()
+    }
+}
+
+pub fn read_wav(path: &Path) -> anyhow::Result<Pcm> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channel_count = usize::from(spec.channels);
+
+    let interleaved = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let mut channels = vec![Vec::with_capacity(interleaved.len() / channel_count); channel_count];
+    for frame in interleaved.chunks_exact(channel_count) {
+        for (channel, &sample) in channels.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+
+    Ok(Pcm {
+        sample_rate: spec.sample_rate,
+        channels,
+    })
+}
+
+pub fn decode_ogg(ogg: &[u8]) -> anyhow::Result<Pcm> {
+    let mut decoder = VorbisDecoder::new(Cursor::new(ogg))?;
+    let sample_rate = decoder.sampling_frequency().get();
+    let mut channels = vec![Vec::new(); usize::from(decoder.channels().get())];
+
+    while let Some(block) = decoder.decode_audio_block()? {
+        for (channel, samples) in channels.iter_mut().zip(block.samples()) {
+            channel.extend_from_slice(samples);
+        }
+    }
+
+    Ok(Pcm {
+        sample_rate,
+        channels,
+    })
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10_f32.powf(db / 20.0)
+}
+
+fn peak(pcm: &Pcm) -> f32 {
+    pcm.channels
+        .iter()
+        .flatten()
+        .fold(0.0, |peak, sample| sample.abs().max(peak))
+}
+
+fn apply_gain(pcm: &mut Pcm, gain: f32) {
+    pcm.channels
+        .iter_mut()
+        .flatten()
+        .for_each(|sample| *sample *= gain);
+}
+
+/// Cuts frames from the start and end where every channel is quieter than `threshold_db`
+pub fn trim(pcm: &mut Pcm, threshold_db: f32) {
+    let threshold = db_to_gain(threshold_db);
+    let is_loud = |frame: usize| {
+        pcm.channels
+            .iter()
+            .any(|channel| channel[frame].abs() > threshold)
+    };
+
+    let Some(start) = (0..pcm.frames()).find(|&frame| is_loud(frame)) else {
+        // All silence, leave it be rather than writing an empty sound
+        return;
+    };
+    let end = (0..pcm.frames())
+        .rfind(|&frame| is_loud(frame))
+        .map_or(pcm.frames(), |end| end + 1);
+
+    for channel in pcm.channels.iter_mut() {
+        channel.truncate(end);
+        channel.drain(..start);
+    }
+}
+
+pub fn downmix_mono(pcm: &mut Pcm) {
+    if pcm.channels.len() <= 1 {
+        return;
+    }
+
+    let channel_count = pcm.channels.len() as f32;
+    let mono = (0..pcm.frames())
+        .map(|frame| {
+            pcm.channels
+                .iter()
+                .map(|channel| channel[frame])
+                .sum::<f32>()
+                / channel_count
+        })
+        .collect();
+    pcm.channels = vec![mono];
+}
+
+pub fn normalise_peak(pcm: &mut Pcm, target_db: f32) {
+    let peak = peak(pcm);
+    if peak > 0.0 {
+        apply_gain(pcm, db_to_gain(target_db) / peak);
+    }
+}
+
+/// Normalises integrated loudness (EBU R128) to `target_lufs`, backing off if that would clip
+pub fn normalise_lufs(pcm: &mut Pcm, target_lufs: f32) -> anyhow::Result<()> {
+    let mut meter =
+        ebur128::EbuR128::new(pcm.channels.len() as u32, pcm.sample_rate, ebur128::Mode::I)?;
+    let planar = pcm.channels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    meter.add_frames_planar_f32(&planar)?;
+    let loudness = meter.loudness_global()?;
+
+    // Too short or too quiet to measure
+    if !loudness.is_finite() {
+        return Ok(());
+    }
+
+    let gain = db_to_gain(target_lufs - loudness as f32);
+    let peak = peak(pcm);
+    let gain = if peak * gain > 1.0 { 1.0 / peak } else { gain };
+    apply_gain(pcm, gain);
+    Ok(())
+}
+
+/// Runs the configured steps in order: trim, downmix, then normalise
+pub(crate) fn process(pcm: &mut Pcm, processing: &ProcessingConfig) -> anyhow::Result<()> {
+    if let Some(Trim::BelowDb(threshold_db)) = processing.trim {
+        trim(pcm, threshold_db);
+    }
+    if processing.mono == Some(true) {
+        downmix_mono(pcm);
+    }
+    match processing.normalise {
+        Some(Normalise::Lufs(target_lufs)) => normalise_lufs(pcm, target_lufs)?,
+        Some(Normalise::Peak(target_db)) => normalise_peak(pcm, target_db),
+        Some(Normalise::Off) | None => {}
+    }
+    Ok(())
+}
+
+/// Decodes an .ogg, resamples and processes it and re-encodes it, sounds that need neither are returned untouched
+pub(crate) fn process_ogg(
+    ogg: Vec<u8>,
+    processing: &ProcessingConfig,
+    encoding: &EncodingConfig,
+) -> anyhow::Result<Vec<u8>> {
+    if !processing.does_anything() && encoding.sample_rate.is_none() {
+        return Ok(ogg);
+    }
+
+    let mut pcm = decode_ogg(&ogg).context("Failed to decode")?;
+    let resample_to = encoding
+        .sample_rate
+        .filter(|&sample_rate| sample_rate != pcm.sample_rate);
+    if !processing.does_anything() && resample_to.is_none() {
+        return Ok(ogg);
+    }
+
+    // Resampled first, same as .wav files are when they're converted
+    if let Some(sample_rate) = resample_to {
+        pcm = resample(pcm, sample_rate).context("Failed to resample")?;
+    }
+    process(&mut pcm, processing)?;
+    encode_ogg(&pcm, encoding.quality).context("Failed to encode")
+}
+
+pub fn resample(pcm: Pcm, sample_rate: u32) -> anyhow::Result<Pcm> {
+    if pcm.sample_rate == sample_rate || pcm.frames() == 0 {
+        return Ok(Pcm { sample_rate, ..pcm });
+    }
+
+    let channel_count = pcm.channels.len();
+    let frames = pcm.frames();
+    let mut resampler = Async::<f32>::new_sinc(
+        f64::from(sample_rate) / f64::from(pcm.sample_rate),
+        1.0,
+        &SincInterpolationParameters {
+            sinc_len: 128,
+            f_cutoff: None,
+            interpolation: SincInterpolationType::Cubic,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        },
+        1024,
+        channel_count,
+        FixedAsync::Input,
+    )?;
+
+    let input = SequentialSliceOfVecs::new(&pcm.channels, channel_count, frames)?;
+    let output = resampler.process_all(&input, frames, None)?;
+
+    let channels = (0..channel_count)
+        .map(|channel| {
+            (0..output.frames())
+                .map(|frame| output.read_sample(channel, frame).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    Ok(Pcm {
+        sample_rate,
+        channels,
+    })
+}
+
+pub fn encode_ogg(pcm: &Pcm, quality: f32) -> anyhow::Result<Vec<u8>> {
+    let sample_rate = NonZeroU32::new(pcm.sample_rate).context("Sample rate is 0")?;
+    let channels = u8::try_from(pcm.channels.len())
+        .ok()
+        .and_then(NonZeroU8::new)
+        .context("Unsupported channel count")?;
+
+    // Fixed serial so the same input always encodes to the same bytes
+    let mut encoder = VorbisEncoderBuilder::new_with_serial(sample_rate, channels, Vec::new(), 0)
+        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: quality,
+        })
+        .build()?;
+
+    const BLOCK_SIZE: usize = 1024;
+    for start in (0..pcm.frames()).step_by(BLOCK_SIZE) {
+        let end = (start + BLOCK_SIZE).min(pcm.frames());
+        let block = pcm
+            .channels
+            .iter()
+            .map(|channel| &channel[start..end])
+            .collect::<Vec<_>>();
+        encoder.encode_audio_block(&block)?;
+    }
+
+    Ok(encoder.finish()?)
+}
+
+/// Converts a .wav to an .ogg at `ogg_path` with whichever backend the config asks for
+pub(crate) fn wav_to_ogg(
+    wav_path: &Path,
+    ogg_path: &Path,
+    encoding: &EncodingConfig,
+) -> anyhow::Result<()> {
+    match encoding.backend {
+        EncoderBackend::Native => {
+            let mut pcm = read_wav(wav_path)
+                .with_context(|| format!("Failed to decode {}", wav_path.display()))?;
+            if let Some(sample_rate) = encoding.sample_rate {
+                pcm = resample(pcm, sample_rate)
+                    .with_context(|| format!("Failed to resample {}", wav_path.display()))?;
+            }
+            let ogg = encode_ogg(&pcm, encoding.quality)
+                .with_context(|| format!("Failed to encode {}", wav_path.display()))?;
+            std::fs::write(ogg_path, ogg)
+                .with_context(|| format!("Failed to write {}", ogg_path.display()))
+        }
+        EncoderBackend::Ffmpeg => ffmpeg(wav_path, ogg_path, encoding),
+    }
+}
+
+#[cfg(feature = "ffmpeg")]
+fn ffmpeg(wav_path: &Path, ogg_path: &Path, encoding: &EncodingConfig) -> anyhow::Result<()> {
+    let mut command = std::process::Command::new("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-i")
+        .arg(wav_path)
+        .args(["-c:a", "libvorbis", "-q:a"])
+        // ffmpeg's libvorbis quality goes -1 to 10 rather than -0.1 to 1
+        .arg((encoding.quality * 10.0).to_string());
+    if let Some(sample_rate) = encoding.sample_rate {
+        command.arg("-ar").arg(sample_rate.to_string());
+    }
+    let output = command
+        .arg(ogg_path)
+        .output()
+        .context("Failed to run ffmpeg")?;
+
+    if !output.status.success() {
+        bail!(
+            "ffmpeg failed to convert {} ({}):\n{}",
+            wav_path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn ffmpeg(wav_path: &Path, _ogg_path: &Path, _encoding: &EncodingConfig) -> anyhow::Result<()> {
+    bail!(
+        "Can't convert {} with ffmpeg, this build doesn't have the ffmpeg feature",
+        wav_path.display()
+    )
+}
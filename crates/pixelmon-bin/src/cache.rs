@@ -0,0 +1,94 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use itertools::Itertools;
+
+use crate::{
+    config::{Config, SoundSourceKind},
+    conversion_cache::ConversionCache,
+    resource::wav_files,
+};
+
+/// Each conversion cache directory in the config, with every .wav that converts into it
+fn caches(config: &Config) -> anyhow::Result<BTreeMap<PathBuf, Vec<PathBuf>>> {
+    let mut caches = BTreeMap::<PathBuf, Vec<PathBuf>>::new();
+    for sound_source in &config.sound_sources {
+        if let SoundSourceKind::Resource { converted_path, .. } = &sound_source.kind {
+            caches
+                .entry(converted_path.clone())
+                .or_default()
+                .extend(wav_files(&sound_source.path)?);
+        }
+    }
+    Ok(caches)
+}
+
+/// Deletes cached conversions that no longer match a .wav with the current encoding settings
+pub fn prune(config_path: &Path) -> anyhow::Result<ExitCode> {
+    let config = Config::load(config_path)?;
+
+    for (root, wav_paths) in caches(&config)? {
+        let cache = ConversionCache::open(root.clone(), config.encoding.clone())?;
+        let removed = cache.prune(&wav_paths)?;
+        println!("{}: removed {removed} stale files", root.display());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Throws away every cached conversion and converts every .wav again
+pub fn rebuild(config_path: &Path) -> anyhow::Result<ExitCode> {
+    let config = Config::load(config_path)?;
+
+    let mut failures = Vec::new();
+    for (root, wav_paths) in caches(&config)? {
+        let cache = ConversionCache::open(root.clone(), config.encoding.clone())?;
+        cache.clear()?;
+
+        let chunk_size = wav_paths.len().div_ceil(config.num_threads.get()).max(1);
+        let cache_failures = std::thread::scope(|s| {
+            let handles = wav_paths
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let cache = &cache;
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|wav_path| {
+                                cache
+                                    .get_or_convert(wav_path)
+                                    .err()
+                                    .map(|reason| (wav_path.clone(), reason))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        println!(
+            "{}: converted {} of {} files",
+            root.display(),
+            wav_paths.len() - cache_failures.len(),
+            wav_paths.len()
+        );
+        failures.extend(cache_failures);
+    }
+
+    if failures.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("{} files failed to convert:", failures.len());
+        for (wav_path, reason) in failures.iter().sorted_by(|a, b| a.0.cmp(&b.0)) {
+            eprintln!("  {}: {reason:#}", wav_path.display());
+        }
+        Ok(ExitCode::FAILURE)
+    }
+}
@@ -2,43 +2,569 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     num::NonZero,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
+use anyhow::{Context, bail};
+use itertools::Itertools;
+use pixelmon_types::species_data::{Gender, SpeciesData};
+
+use schemars::JsonSchema;
 use serde::{
-    Deserialize,
+    Deserialize, Serialize,
     de::{Unexpected, Visitor},
 };
 use serde_json::value::RawValue;
 
-// Realistically I think a lot of these config options are worthless. Skip form names basically just exists for slowbro/king, treat as base is just there for ogerpon (but can be done with dumb_insert or treat_as_base_all)
+use crate::{config_layers, subtitles::SubtitleLanguage};
+
+// Realistically I think a lot of these config options are worthless. Skip form names basically just exists for slowbro/king
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Config {
-    /// Source .jar
+    /// Source .jar, relative paths are resolved against the config file's directory
     pub source: PathBuf,
     pub version_number: String,
     /// Number of threads to have work on the species data at a time
     pub num_threads: NonZero<usize>,
     /// JSON to place in the resource pack's pack.mcmeta
+    #[schemars(with = "serde_json::Value")]
     pub resource_pack_mcmeta: Box<RawValue>,
     /// Text to place in the credits.txt
     pub credits: String,
     /// JSON to place in the data pack's pack.mcmeta
+    #[schemars(with = "serde_json::Value")]
     pub data_pack_mcmeta: Box<RawValue>,
-    /// Pokemon to "play dumb" for, grabbing the first sound file we can get, shoving it in the first palette and calling it a day.
+    /// Pokemon to "play dumb" for, grabbing the first sound file we can get, shoving it in the first form's palettes
+    /// (all of them, or just the first with `palette_mode: first`) and calling it a day.
     pub dumb_insert: HashSet<String>,
     /// Form names to skip completely for ALL pokemon
     pub skip_form_names_all: HashSet<String>,
     /// Form names to skip completely for specific pokemon (excluding those marked as a base form)
     pub skip_form_names: HashMap<String, ConfigForm>,
-    /// Form names to treat as 'base' for all pokemon (i.e no form)
-    pub treat_as_base_all: HashSet<String>,
-    /// Form names to treat as 'base' for specific Pokemon
-    pub treat_as_base: HashMap<String, String>,
+    /// Which form names are really the base form, and what other names a form goes by
+    #[serde(default)]
+    pub forms: FormAliases,
     /// Files to deep copy at the last step, to overwrite files in pixelmon that don't meet the {pokemon}-{form} naming convention
     pub deep_copy: Vec<(String, String)>,
+    /// Directories to pull sounds from, tried in ascending priority order. Relative paths are resolved against the config file's directory
+    pub sound_sources: Vec<SoundSourceConfig>,
+    /// JSON file of per-source overrides for sounds that can't be found by name, relative to the config file's directory
+    #[serde(default)]
+    pub manual_matches: Option<PathBuf>,
+    /// How much of the jar's sounds.json to put in the resource pack
+    #[serde(default)]
+    pub sound_registry_mode: SoundRegistryMode,
+    /// Subtitles to give the cries we register, none if left out
+    #[serde(default)]
+    pub subtitles: Option<SubtitleConfig>,
+    /// Which palettes get a form's cry
+    #[serde(default)]
+    pub palette_mode: PaletteMode,
+    /// Palettes or genders that get their own cry instead of the form's, by pokemon
+    #[serde(default)]
+    pub palette_sounds: HashMap<String, Vec<PaletteSound>>,
+    /// How .wav sounds are turned into .ogg
+    #[serde(default)]
+    pub encoding: EncodingConfig,
+    /// Audio processing applied to every cry before it goes in the pack
+    #[serde(default)]
+    pub processing: ProcessingConfig,
+    /// Per pokemon changes to `processing`, only the steps given are overridden
+    #[serde(default)]
+    pub species_processing: HashMap<String, ProcessingConfig>,
+    /// How .wav sounds are matched to pokemon by name
+    #[serde(default)]
+    pub fuzzy_matching: FuzzyMatchingConfig,
+    /// Where the packs and reports are written and what they're called
+    #[serde(default)]
+    pub output: OutputConfig,
+}
+
+impl Config {
+    /// Loads a config along with everything it extends, see [`crate::config_layers`]
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let resolved = config_layers::load(path)?;
+        // The layers are merged by now so there's no line to point at, just the field
+        let mut config: Config = serde_path_to_error::deserialize(resolved)
+            .with_context(|| format!("Failed to parse config {}", path.display()))?;
+
+        config
+            .sound_sources
+            .sort_by_key(|sound_source| sound_source.priority);
+
+        if !(-0.1..=1.0).contains(&config.encoding.quality) {
+            bail!(
+                "Encoding quality {} is outside of -0.1 to 1",
+                config.encoding.quality
+            );
+        }
+        if config.encoding.backend == EncoderBackend::Ffmpeg && !cfg!(feature = "ffmpeg") {
+            bail!("Encoding backend is ffmpeg but this build doesn't have the ffmpeg feature");
+        }
+
+        Ok(config)
+    }
+
+    /// Whether a form is configured to be left alone entirely
+    pub fn skips_form(&self, pokemon: &str, form: &str) -> bool {
+        if self.skip_form_names_all.contains(form) {
+            return true;
+        }
+
+        match self.skip_form_names.get(pokemon) {
+            Some(ConfigForm::All) => true,
+            Some(ConfigForm::Form(to_skip)) => to_skip.contains(form),
+            Some(ConfigForm::Except(dont_skip)) => !dont_skip.contains(form),
+            None => false,
+        }
+    }
+
+    /// The processing steps for a pokemon's cries, with any species override layered on top
+    pub fn processing_for(&self, pokemon: &str) -> ProcessingConfig {
+        match self.species_processing.get(pokemon) {
+            Some(species) => self.processing.overridden_by(species),
+            None => self.processing.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FormAliases {
+    /// Form names that are just the base form (i.e no form) for every pokemon
+    #[serde(default = "FormAliases::default_base")]
+    pub base: HashSet<String>,
+    /// Form names to look sounds up and name them by instead of the jar's, for every pokemon
+    #[serde(default)]
+    pub canonical: HashMap<String, String>,
+    /// Other names sound sources and the jar might have a form's cry under, by canonical name.
+    /// Tried in order when nothing is found under the canonical name, and the jar's own files under them are replaced too
+    #[serde(default)]
+    pub aliases: HashMap<String, Vec<String>>,
+    /// Per pokemon additions, these win over the ones for every pokemon
+    #[serde(default)]
+    pub species: HashMap<String, SpeciesFormAliases>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SpeciesFormAliases {
+    /// Replaces the base form inferred from the jar's species data, an empty list keeps every form suffixed
+    #[serde(default)]
+    pub base: Option<HashSet<String>>,
+    #[serde(default)]
+    pub canonical: HashMap<String, String>,
+    #[serde(default)]
+    pub aliases: HashMap<String, Vec<String>>,
+}
+
+impl FormAliases {
+    fn default_base() -> HashSet<String> {
+        HashSet::from(["base".to_string()])
+    }
+
+    /// The form name sounds should be looked up and named by, `None` for forms that are the base form.
+    /// `inferred_base` is the base form from the jar's species data, used unless config gives the pokemon its own
+    pub fn canonical<'a>(
+        &'a self,
+        pokemon: &str,
+        inferred_base: Option<&str>,
+        form: &'a str,
+    ) -> Option<&'a str> {
+        let species = self.species.get(pokemon);
+        let is_base = match species.and_then(|species| species.base.as_ref()) {
+            Some(base) => base.contains(form),
+            None => inferred_base == Some(form),
+        };
+        if is_base || self.base.contains(form) {
+            return None;
+        }
+
+        Some(
+            species
+                .and_then(|species| species.canonical.get(form))
+                .or_else(|| self.canonical.get(form))
+                .map_or(form, String::as_str),
+        )
+    }
+
+    /// Whether the base forms config gives a pokemon disagree with the jar's species data
+    pub fn base_form_warning(&self, species: &SpeciesData) -> Option<String> {
+        let pokemon = species.name.to_lowercase();
+        let base = self.species.get(&pokemon)?.base.as_ref()?;
+        let inferred = species.inferred_base_form()?;
+        if base.contains(inferred) || self.base.contains(inferred) {
+            return None;
+        }
+
+        Some(if base.is_empty() {
+            format!(
+                "{pokemon}: config keeps every form suffixed but the jar defaults to {inferred}"
+            )
+        } else {
+            format!(
+                "{pokemon}: config makes {} the base form but the jar defaults to {inferred}",
+                base.iter().sorted().join(", ")
+            )
+        })
+    }
+
+    /// Other names for a canonical form, the pokemon's own first
+    pub fn aliases<'a>(&'a self, pokemon: &str, form: &str) -> Vec<&'a str> {
+        self.species
+            .get(pokemon)
+            .and_then(|species| species.aliases.get(form))
+            .into_iter()
+            .chain(self.aliases.get(form))
+            .flatten()
+            .map(String::as_str)
+            .unique()
+            .collect()
+    }
+}
+
+impl Default for FormAliases {
+    fn default() -> Self {
+        Self {
+            base: Self::default_base(),
+            canonical: HashMap::new(),
+            aliases: HashMap::new(),
+            species: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SoundSourceConfig {
+    /// Name used when reporting where a sound came from
+    pub name: String,
+    /// Root directory of the sound library, or the zip file for `pack_zip`
+    pub path: PathBuf,
+    /// Lower priorities are tried first
+    pub priority: u32,
+    pub kind: SoundSourceKind,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum SoundSourceKind {
+    /// Pixelmon EX style `{pokemon}-{form}.ogg` files
+    Expixel,
+    /// `{dex} - {Name} ({Form}).wav` files, converted to .ogg and cached in `converted_path`
+    Resource {
+        converted_path: PathBuf,
+        /// Words to take out of the bracketed form part of file names, e.g. `form` so `(Curly Form)` reads as `curly`
+        #[serde(default)]
+        strip_from_forms: Vec<String>,
+    },
+    /// A plain folder of `{pokemon}-{form}.ogg` files
+    Folder,
+    /// A resource pack zip from a previous build
+    PackZip,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SubtitleConfig {
+    /// Wording for the en_us lang file
+    pub en_us: SubtitleLanguage,
+    /// Language code to a JSON file worded like `en_us`, relative to the config file's directory
+    #[serde(default)]
+    pub extra_languages: HashMap<String, PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct EncodingConfig {
+    #[serde(default)]
+    pub backend: EncoderBackend,
+    /// Vorbis quality from -0.1 to 1, 0.5 is roughly 160kbps
+    #[serde(default = "EncodingConfig::default_quality")]
+    pub quality: f32,
+    /// Resample to this rate, left alone if not given
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+}
+
+impl EncodingConfig {
+    fn default_quality() -> f32 {
+        0.5
+    }
+}
+
+impl Default for EncodingConfig {
+    fn default() -> Self {
+        Self {
+            backend: Default::default(),
+            quality: Self::default_quality(),
+            sample_rate: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FuzzyMatchingConfig {
+    /// Lowest Jaro-Winkler similarity that counts as a match
+    #[serde(default = "FuzzyMatchingConfig::default_threshold")]
+    pub threshold: f64,
+    /// A match is ambiguous when the runner up scores within this much of it
+    #[serde(default = "FuzzyMatchingConfig::default_ambiguity_margin")]
+    pub ambiguity_margin: f64,
+    /// How many of the best scoring names to keep in the report
+    #[serde(default = "FuzzyMatchingConfig::default_candidates")]
+    pub candidates: usize,
+    /// Fail any cry with an ambiguous match, pin it with a manual match to get past it
+    #[serde(default)]
+    pub fail_on_ambiguous: bool,
+}
+
+impl FuzzyMatchingConfig {
+    fn default_threshold() -> f64 {
+        0.8
+    }
+
+    fn default_ambiguity_margin() -> f64 {
+        0.02
+    }
+
+    fn default_candidates() -> usize {
+        3
+    }
+}
+
+impl Default for FuzzyMatchingConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Self::default_threshold(),
+            ambiguity_margin: Self::default_ambiguity_margin(),
+            candidates: Self::default_candidates(),
+            fail_on_ambiguous: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct OutputConfig {
+    /// Directory to write everything to, relative to the config file's directory. The working directory if left out
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// File names, `{version}` is replaced with `version_number`
+    #[serde(default = "OutputConfig::default_resource_pack")]
+    pub resource_pack: String,
+    #[serde(default = "OutputConfig::default_data_pack")]
+    pub data_pack: String,
+    #[serde(default = "OutputConfig::default_report")]
+    pub report: String,
+    /// Without an extension, the coverage report is written as both .json and .csv
+    #[serde(default = "OutputConfig::default_coverage")]
+    pub coverage: String,
+}
+
+impl OutputConfig {
+    fn default_resource_pack() -> String {
+        "pixelmon_cry_replacer_resource_pack_{version}.zip".to_string()
+    }
+
+    fn default_data_pack() -> String {
+        "pixelmon_cry_replacer_data_pack_{version}.zip".to_string()
+    }
+
+    fn default_report() -> String {
+        "pack_report_{version}.json".to_string()
+    }
+
+    fn default_coverage() -> String {
+        "coverage_{version}".to_string()
+    }
+
+    /// `self` with anything given on the command line replaced
+    pub fn overridden_by(&self, args: &OutputArgs) -> OutputConfig {
+        OutputConfig {
+            dir: args.output_dir.clone().or_else(|| self.dir.clone()),
+            resource_pack: args
+                .resource_pack_name
+                .clone()
+                .unwrap_or_else(|| self.resource_pack.clone()),
+            data_pack: args
+                .data_pack_name
+                .clone()
+                .unwrap_or_else(|| self.data_pack.clone()),
+            report: args
+                .report_name
+                .clone()
+                .unwrap_or_else(|| self.report.clone()),
+            coverage: args
+                .coverage_name
+                .clone()
+                .unwrap_or_else(|| self.coverage.clone()),
+        }
+    }
+
+    /// Where to write a file named by one of the templates
+    pub fn path(&self, template: &str, version: &str) -> anyhow::Result<PathBuf> {
+        let name = template.replace("{version}", version);
+        if name.contains(['{', '}']) {
+            bail!("Output name {template} has a placeholder other than {{version}}");
+        }
+        Ok(self.dir.as_deref().unwrap_or(Path::new("")).join(name))
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            resource_pack: Self::default_resource_pack(),
+            data_pack: Self::default_data_pack(),
+            report: Self::default_report(),
+            coverage: Self::default_coverage(),
+        }
+    }
+}
+
+/// Command line overrides for [`OutputConfig`], relative to the working directory rather than the config's
+#[derive(Debug, Clone, Default, clap::Args)]
+pub(crate) struct OutputArgs {
+    /// Directory to write the packs and reports to
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+    /// Resource pack file name, `{version}` is replaced with the config's version number
+    #[arg(long)]
+    pub resource_pack_name: Option<String>,
+    /// Data pack file name, `{version}` is replaced with the config's version number
+    #[arg(long)]
+    pub data_pack_name: Option<String>,
+    /// Pack report file name, `{version}` is replaced with the config's version number
+    #[arg(long)]
+    pub report_name: Option<String>,
+    /// Coverage report file name without an extension, `{version}` is replaced with the config's version number
+    #[arg(long)]
+    pub coverage_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProcessingConfig {
+    #[serde(default)]
+    pub trim: Option<Trim>,
+    #[serde(default)]
+    pub normalise: Option<Normalise>,
+    /// Downmix to mono, Minecraft only attenuates mono sounds with distance
+    #[serde(default)]
+    pub mono: Option<bool>,
+}
+
+impl ProcessingConfig {
+    /// `self` with every step `other` sets replaced
+    pub fn overridden_by(&self, other: &ProcessingConfig) -> ProcessingConfig {
+        ProcessingConfig {
+            trim: other.trim.or(self.trim),
+            normalise: other.normalise.or(self.normalise),
+            mono: other.mono.or(self.mono),
+        }
+    }
+
+    /// Whether any step would change the sound, if not it can be copied as is
+    pub fn does_anything(&self) -> bool {
+        matches!(self.trim, Some(Trim::BelowDb(_)))
+            || matches!(
+                self.normalise,
+                Some(Normalise::Lufs(_) | Normalise::Peak(_))
+            )
+            || self.mono == Some(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Trim {
+    Off,
+    /// Cut leading and trailing audio quieter than this many dBFS
+    BelowDb(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Normalise {
+    Off,
+    /// Integrated loudness to aim for, turned down if it would clip
+    Lufs(f32),
+    /// Peak level in dBFS to aim for
+    Peak(f32),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EncoderBackend {
+    /// Decode, resample and encode in-process
+    #[default]
+    Native,
+    /// Shell out to ffmpeg, needs the `ffmpeg` cargo feature
+    Ffmpeg,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteMode {
+    /// Every palette of every gender, so shinies and female variants cry too
+    #[default]
+    All,
+    /// Only the first palette of the first gender, how packs used to be built
+    First,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PaletteSound {
+    /// Form (as named in the jar) this applies to, every form if left out
+    #[serde(default)]
+    pub form: Option<String>,
+    /// Gender this applies to, every gender if left out
+    #[serde(default)]
+    pub gender: Option<Gender>,
+    /// Palette name (e.g. `shiny`) this applies to, every palette if left out
+    #[serde(default)]
+    pub palette: Option<String>,
+    /// Sound file (name without extension) to use, taken from the first sound source that has it
+    pub sound: String,
+}
+
+impl PaletteSound {
+    pub fn matches(&self, form: &str, gender: Gender, palette: &str) -> bool {
+        self.form.as_deref().is_none_or(|f| f == form)
+            && self.gender.is_none_or(|g| g == gender)
+            && self.palette.as_deref().is_none_or(|p| p == palette)
+    }
+
+    /// What the cry is registered and named as in place of a form name, e.g. `galarian-shiny` or `female-{sound}`.
+    /// Ends in the palette, or `sound` without one, so it never lands on the form's or a gender's own cry
+    pub fn sound_form_name(&self, form_name: Option<&str>) -> String {
+        [
+            form_name,
+            self.gender.and_then(Gender::sound_name),
+            Some(self.palette.as_deref().unwrap_or(&self.sound)),
+        ]
+        .into_iter()
+        .flatten()
+        .join("-")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundRegistryMode {
+    /// Only the cries we registered
+    #[default]
+    Changed,
+    /// The jar's whole sounds.json with our cries layered on top
+    Merged,
+    /// Only the cries that differ from the jar's, checking every sound they point at exists
+    Overlay,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -104,3 +630,27 @@ impl<'de> Deserialize<'de> for ConfigForm {
         deserializer.deserialize_any(Vis)
     }
 }
+
+impl JsonSchema for ConfigForm {
+    fn schema_name() -> Cow<'static, str> {
+        "ConfigForm".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "\"all\" for every form, a list of form names, or every form except the ones listed",
+            "anyOf": [
+                { "const": "all" },
+                { "type": "array", "items": { "type": "string" }, "uniqueItems": true },
+                {
+                    "type": "object",
+                    "properties": {
+                        "except": { "type": "array", "items": { "type": "string" }, "uniqueItems": true }
+                    },
+                    "required": ["except"],
+                    "additionalProperties": false
+                }
+            ]
+        })
+    }
+}
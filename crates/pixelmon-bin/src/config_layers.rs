@@ -0,0 +1,433 @@
+//! Configs are JSON, or TOML or YAML going by the file extension, and can have a `$schema` for editors to pick up.
+//!
+//! Configs can `extends` another config (relative to their own directory) and only give what differs from it.
+//! Layers are merged as JSON before being parsed as a [`Config`]:
+//! - `dumb_insert`, `skip_form_names_all`, `deep_copy` and `forms.base` are sets, a layer's entries are added to
+//!   the inherited ones
+//! - `sound_sources` are matched up by name, a layer's source replaces the inherited one with the same name
+//! - Other objects are merged key by key, with a layer's value replacing the inherited one unless both are
+//!   objects. `resource_pack_mcmeta` and `data_pack_mcmeta` are replaced whole
+//! - Anything else is replaced
+//!
+//! `remove` takes things out of the inherited config before the layer is merged in. It's shaped like the config:
+//! a list of entries for sets, names for `sound_sources`, keys for objects, `null` to drop a field entirely, or an
+//! object to remove from deeper in. Removing something that isn't inherited is an error since it's likely a typo.
+//! TOML has no `null`, so dropping a whole top level field needs a JSON or YAML layer
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{Context, bail};
+use serde_json::{Value, json};
+
+use crate::config::Config;
+
+const SETS: &[&[&str]] = &[
+    &["dumb_insert"],
+    &["skip_form_names_all"],
+    &["deep_copy"],
+    &["forms", "base"],
+];
+
+/// Lists whose entries are matched up by a field rather than compared whole
+const KEYED_LISTS: &[(&[&str], &str)] = &[(&["sound_sources"], "name")];
+
+/// Objects that are replaced rather than merged, since they're passed through as is
+const REPLACED: &[&[&str]] = &[&["resource_pack_mcmeta"], &["data_pack_mcmeta"]];
+
+fn is_path(path: &[String], target: &[&str]) -> bool {
+    path.iter().map(String::as_str).eq(target.iter().copied())
+}
+
+fn list_key(path: &[String]) -> Option<&'static str> {
+    KEYED_LISTS
+        .iter()
+        .find(|(target, _)| is_path(path, target))
+        .map(|(_, key)| *key)
+}
+
+/// Loads a config with everything it extends merged in, paths resolved and `extends` and `remove` gone,
+/// so the result is a config that stands on its own
+pub(crate) fn load(path: &Path) -> anyhow::Result<Value> {
+    load_layer(path, &mut Vec::new())
+}
+
+fn load_layer(path: &Path, chain: &mut Vec<PathBuf>) -> anyhow::Result<Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to open config {}", path.display()))?;
+    if chain.contains(&canonical) {
+        bail!("Config {} ends up extending itself", path.display());
+    }
+    chain.push(canonical);
+
+    let mut layer =
+        read_layer(path).with_context(|| format!("Failed to parse config {}", path.display()))?;
+    let Value::Object(fields) = &mut layer else {
+        bail!("Config {} isn't an object", path.display());
+    };
+    fields.remove("$schema");
+    let extends = fields.remove("extends");
+    let removal = fields.remove("remove");
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    resolve_paths(&mut layer, base_dir);
+
+    let Some(extends) = extends else {
+        if removal.is_some() {
+            bail!(
+                "Config {} has remove but doesn't extend anything",
+                path.display()
+            );
+        }
+        return Ok(layer);
+    };
+    let extends = extends
+        .as_str()
+        .with_context(|| format!("extends in {} should be a path", path.display()))?;
+
+    let mut config = load_layer(&base_dir.join(extends), chain)
+        .with_context(|| format!("Failed to load {extends}, which {} extends", path.display()))?;
+    if let Some(removal) = removal {
+        remove(&mut Vec::new(), &mut config, removal)
+            .with_context(|| format!("Failed to apply remove in {}", path.display()))?;
+    }
+    merge(&mut Vec::new(), &mut config, layer);
+    Ok(config)
+}
+
+fn read_layer(path: &Path) -> anyhow::Result<Value> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to open config {}", path.display()))?;
+    let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+    Ok(match extension.to_lowercase().as_str() {
+        "toml" => toml::from_str(&text)?,
+        "yaml" | "yml" => serde_yaml_ng::from_str(&text)?,
+        _ => serde_json::from_str(&text)?,
+    })
+}
+
+/// Makes the config's relative paths relative to where the process is running instead
+fn resolve_paths(config: &mut Value, base_dir: &Path) {
+    let join = |value: &mut Value| {
+        if let Value::String(path) = value {
+            *path = base_dir.join(&*path).to_string_lossy().into_owned();
+        }
+    };
+
+    config.pointer_mut("/source").into_iter().for_each(join);
+    config
+        .pointer_mut("/manual_matches")
+        .into_iter()
+        .for_each(join);
+    config.pointer_mut("/output/dir").into_iter().for_each(join);
+    if let Some(Value::Array(sound_sources)) = config.pointer_mut("/sound_sources") {
+        for sound_source in sound_sources {
+            sound_source.pointer_mut("/path").into_iter().for_each(join);
+            sound_source
+                .pointer_mut("/kind/resource/converted_path")
+                .into_iter()
+                .for_each(join);
+        }
+    }
+    if let Some(Value::Object(languages)) = config.pointer_mut("/subtitles/extra_languages") {
+        languages.values_mut().for_each(join);
+    }
+}
+
+fn merge(path: &mut Vec<String>, inherited: &mut Value, layer: Value) {
+    let is_set = SETS.iter().any(|set| is_path(path, set));
+    let list_key = list_key(path);
+    let is_replaced = REPLACED.iter().any(|replaced| is_path(path, replaced));
+
+    match (inherited, layer) {
+        (Value::Array(inherited), Value::Array(layer)) if is_set => {
+            for entry in layer {
+                if !inherited.contains(&entry) {
+                    inherited.push(entry);
+                }
+            }
+        }
+        (Value::Array(inherited), Value::Array(layer)) if list_key.is_some() => {
+            let key = list_key.unwrap_or_default();
+            for entry in layer {
+                match inherited.iter_mut().find(|existing| {
+                    existing.get(key).is_some() && existing.get(key) == entry.get(key)
+                }) {
+                    Some(existing) => *existing = entry,
+                    None => inherited.push(entry),
+                }
+            }
+        }
+        (Value::Object(inherited), Value::Object(layer)) if !is_replaced => {
+            for (key, value) in layer {
+                match inherited.get_mut(&key) {
+                    Some(existing) => {
+                        path.push(key);
+                        merge(path, existing, value);
+                        path.pop();
+                    }
+                    None => {
+                        inherited.insert(key, value);
+                    }
+                }
+            }
+        }
+        (inherited, layer) => *inherited = layer,
+    }
+}
+
+fn remove(path: &mut Vec<String>, inherited: &mut Value, removal: Value) -> anyhow::Result<()> {
+    let field = path.join(".");
+    match (inherited, removal) {
+        (Value::Array(inherited), Value::Array(removal)) => {
+            let key = list_key(path);
+            for entry in removal {
+                let len = inherited.len();
+                inherited.retain(|existing| match key {
+                    Some(key) => existing.get(key) != Some(&entry),
+                    None => existing != &entry,
+                });
+                if inherited.len() == len {
+                    bail!("{field} doesn't have {entry} to remove");
+                }
+            }
+        }
+        (Value::Object(inherited), Value::Array(keys)) => {
+            for key in keys {
+                let key = key
+                    .as_str()
+                    .with_context(|| format!("Keys to remove from {field} should be strings"))?;
+                if inherited.remove(key).is_none() {
+                    bail!("{field} doesn't have {key} to remove");
+                }
+            }
+        }
+        (Value::Object(inherited), Value::Object(removal)) => {
+            for (key, value) in removal {
+                let at = if field.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{field}.{key}")
+                };
+                if value.is_null() {
+                    if inherited.remove(&key).is_none() {
+                        bail!("Nothing to remove at {at}");
+                    }
+                    continue;
+                }
+                let Some(existing) = inherited.get_mut(&key) else {
+                    bail!("Nothing to remove at {at}");
+                };
+                path.push(key);
+                remove(path, existing, value)?;
+                path.pop();
+            }
+        }
+        (_, removal) => bail!("Can't remove {removal} from {field}"),
+    }
+    Ok(())
+}
+
+/// Prints a config with everything it extends merged in
+pub fn show(config_path: &Path) -> anyhow::Result<ExitCode> {
+    // Loading it properly makes sure the merged config actually parses
+    Config::load(config_path)?;
+    let config = load(config_path)?;
+    println!("{}", serde_json::to_string_pretty(&config)?);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the JSON Schema for a config layer, which is [`Config`]'s plus the fields only layers have
+pub fn print_schema() -> anyhow::Result<ExitCode> {
+    let mut schema = schemars::schema_for!(Config).to_value();
+
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            "$schema".to_string(),
+            json!({ "description": "Schema for editors to check the config against", "type": "string" }),
+        );
+        properties.insert(
+            "extends".to_string(),
+            json!({
+                "description": "Config to layer this one over, relative to this config's directory",
+                "type": "string"
+            }),
+        );
+        properties.insert(
+            "remove".to_string(),
+            json!({
+                "description": "Entries to take out of the config this extends, shaped like the config",
+                "type": "object"
+            }),
+        );
+    }
+
+    // Any file could be a base for others to fill in, loading checks the merged config has everything
+    if let Value::Object(root) = &mut schema {
+        root.remove("required");
+    }
+
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(inherited: Value, layer: Value) -> Value {
+        let mut inherited = inherited;
+        merge(&mut Vec::new(), &mut inherited, layer);
+        inherited
+    }
+
+    fn removed(inherited: Value, removal: Value) -> anyhow::Result<Value> {
+        let mut inherited = inherited;
+        remove(&mut Vec::new(), &mut inherited, removal)?;
+        Ok(inherited)
+    }
+
+    #[test]
+    fn merge_adds_to_sets() {
+        let config = merged(
+            json!({ "dumb_insert": ["a", "b"], "forms": { "base": ["base"] } }),
+            json!({ "dumb_insert": ["b", "c"], "forms": { "base": ["standard"] } }),
+        );
+        assert_eq!(
+            config,
+            json!({ "dumb_insert": ["a", "b", "c"], "forms": { "base": ["base", "standard"] } })
+        );
+    }
+
+    #[test]
+    fn merge_replaces_sound_sources_by_name() {
+        let config = merged(
+            json!({ "sound_sources": [
+                { "name": "a", "priority": 0 },
+                { "name": "b", "priority": 1 },
+            ] }),
+            json!({ "sound_sources": [
+                { "name": "b", "priority": 5 },
+                { "name": "c", "priority": 2 },
+            ] }),
+        );
+        assert_eq!(
+            config,
+            json!({ "sound_sources": [
+                { "name": "a", "priority": 0 },
+                { "name": "b", "priority": 5 },
+                { "name": "c", "priority": 2 },
+            ] })
+        );
+    }
+
+    #[test]
+    fn merge_replaces_mcmeta_whole() {
+        let config = merged(
+            json!({
+                "resource_pack_mcmeta": { "pack": { "pack_format": 1, "description": "old" } },
+                "output": { "dir": "out", "report": "report" },
+            }),
+            json!({
+                "resource_pack_mcmeta": { "pack": { "pack_format": 2 } },
+                "output": { "dir": "elsewhere" },
+            }),
+        );
+        assert_eq!(
+            config,
+            json!({
+                "resource_pack_mcmeta": { "pack": { "pack_format": 2 } },
+                "output": { "dir": "elsewhere", "report": "report" },
+            })
+        );
+    }
+
+    #[test]
+    fn merge_replaces_other_lists() {
+        let config = merged(json!({ "list": [1, 2] }), json!({ "list": [3] }));
+        assert_eq!(config, json!({ "list": [3] }));
+    }
+
+    #[test]
+    fn remove_null_drops_field() {
+        let config = removed(json!({ "a": 1, "b": 2 }), json!({ "a": null })).unwrap();
+        assert_eq!(config, json!({ "b": 2 }));
+    }
+
+    #[test]
+    fn remove_list_takes_out_entries() {
+        let config = removed(
+            json!({
+                "dumb_insert": ["a", "b"],
+                "sound_sources": [{ "name": "a" }, { "name": "b" }],
+                "species_processing": { "a": {}, "b": {} },
+            }),
+            json!({
+                "dumb_insert": ["a"],
+                "sound_sources": ["b"],
+                "species_processing": ["a"],
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "dumb_insert": ["b"],
+                "sound_sources": [{ "name": "a" }],
+                "species_processing": { "b": {} },
+            })
+        );
+    }
+
+    #[test]
+    fn remove_nested() {
+        let config = removed(
+            json!({ "forms": { "base": ["base", "standard"], "aliases": { "galarian": ["galar"] } } }),
+            json!({ "forms": { "base": ["standard"], "aliases": { "galarian": null } } }),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            json!({ "forms": { "base": ["base"], "aliases": {} } })
+        );
+    }
+
+    #[test]
+    fn remove_missing_is_an_error() {
+        let inherited = json!({ "dumb_insert": ["a"], "forms": { "aliases": {} } });
+        let errors = [
+            json!({ "dumb_insert": ["b"] }),
+            json!({ "forms": ["base"] }),
+            json!({ "forms": { "base": null } }),
+            json!({ "output": { "dir": null } }),
+        ]
+        .map(|removal| removed(inherited.clone(), removal).unwrap_err().to_string());
+        assert_eq!(
+            errors,
+            [
+                "dumb_insert doesn't have \"b\" to remove",
+                "forms doesn't have base to remove",
+                "Nothing to remove at forms.base",
+                "Nothing to remove at output",
+            ]
+        );
+    }
+
+    #[test]
+    fn extending_itself_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("config-layers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), r#"{ "extends": "b.toml" }"#).unwrap();
+        std::fs::write(dir.join("b.toml"), r#"extends = "a.json""#).unwrap();
+
+        let error = load(&dir.join("a.json")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            format!("{error:#}").contains("ends up extending itself"),
+            "{error:#}"
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audio::wav_to_ogg,
+    config::{EncoderBackend, EncodingConfig},
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Bump this when conversion changes in a way the key doesn't capture, so old entries stop matching
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    /// Cache key (also the file stem of the .ogg) to what went into it
+    entries: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+    source: PathBuf,
+    backend: EncoderBackend,
+    quality: f32,
+    sample_rate: Option<u32>,
+}
+
+/// Converted .ogg files named by a hash of the .wav and the conversion settings, so a changed
+/// source or setting can never pick up a stale conversion
+pub struct ConversionCache {
+    root: PathBuf,
+    encoding: EncodingConfig,
+    manifest: Mutex<Manifest>,
+}
+
+impl ConversionCache {
+    pub(crate) fn open(root: PathBuf, encoding: EncodingConfig) -> anyhow::Result<Self> {
+        let manifest_path = root.join(MANIFEST_FILE);
+        let manifest = match std::fs::File::open(&manifest_path) {
+            Ok(file) => serde_json::from_reader::<_, Manifest>(file)
+                .with_context(|| format!("Failed to parse {}", manifest_path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Manifest::default(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open {}", manifest_path.display()));
+            }
+        };
+
+        // Entries from another cache version can't be trusted, prune will clear out their files
+        let manifest = if manifest.version == CACHE_VERSION {
+            manifest
+        } else {
+            Manifest {
+                version: CACHE_VERSION,
+                entries: BTreeMap::new(),
+            }
+        };
+
+        Ok(Self {
+            root,
+            encoding,
+            manifest: Mutex::new(manifest),
+        })
+    }
+
+    pub fn key(&self, wav_path: &Path) -> anyhow::Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(CACHE_VERSION.to_le_bytes());
+        hasher.update(format!("{:?}", self.encoding.backend).as_bytes());
+        hasher.update(self.encoding.quality.to_le_bytes());
+        hasher.update(self.encoding.sample_rate.unwrap_or_default().to_le_bytes());
+
+        let mut file = std::fs::File::open(wav_path)
+            .with_context(|| format!("Failed to open {}", wav_path.display()))?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read = file
+                .read(&mut buf)
+                .with_context(|| format!("Failed to read {}", wav_path.display()))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+        }
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{key}.ogg"))
+    }
+
+    /// Path to the converted .ogg, converting the .wav first if the cache doesn't have it
+    pub fn get_or_convert(&self, wav_path: &Path) -> anyhow::Result<PathBuf> {
+        let key = self.key(wav_path)?;
+        let ogg_path = self.path_for(&key);
+
+        if ogg_path.is_file() && self.manifest.lock().unwrap().entries.contains_key(&key) {
+            return Ok(ogg_path);
+        }
+
+        std::fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create {}", self.root.display()))?;
+
+        // Convert next to the real path and rename, so a failed conversion never looks cached. Threads can convert
+        // the same .wav at once, so each conversion gets its own temp file and the last rename just wins
+        let temp_path = self.root.join(format!(
+            "{key}.{}.{}.tmp.ogg",
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        wav_to_ogg(wav_path, &temp_path, &self.encoding)?;
+        std::fs::rename(&temp_path, &ogg_path)
+            .with_context(|| format!("Failed to move {}", temp_path.display()))?;
+
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.entries.insert(
+            key,
+            ManifestEntry {
+                source: wav_path.to_path_buf(),
+                backend: self.encoding.backend,
+                quality: self.encoding.quality,
+                sample_rate: self.encoding.sample_rate,
+            },
+        );
+        self.save_manifest(&manifest)?;
+
+        Ok(ogg_path)
+    }
+
+    /// Deletes every cached file that isn't the current conversion of one of `wav_paths`, returns how many went
+    pub fn prune(&self, wav_paths: &[PathBuf]) -> anyhow::Result<usize> {
+        let live = wav_paths
+            .iter()
+            .map(|wav_path| self.key(wav_path))
+            .collect::<anyhow::Result<HashSet<_>>>()?;
+
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.entries.retain(|key, _| live.contains(key));
+
+        let mut removed = 0;
+        if self.root.is_dir() {
+            for file in self
+                .root
+                .read_dir()
+                .with_context(|| format!("Failed to read {}", self.root.display()))?
+            {
+                let path = file?.path();
+                if path.extension().is_none_or(|extension| extension != "ogg") {
+                    continue;
+                }
+
+                let is_live = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|key| manifest.entries.contains_key(key));
+                if !is_live {
+                    std::fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                    removed += 1;
+                }
+            }
+        }
+
+        self.save_manifest(&manifest)?;
+        Ok(removed)
+    }
+
+    /// Deletes every cached file and the manifest
+    pub fn clear(&self) -> anyhow::Result<()> {
+        self.prune(&[])?;
+        let manifest_path = self.root.join(MANIFEST_FILE);
+        if manifest_path.is_file() {
+            std::fs::remove_file(&manifest_path)
+                .with_context(|| format!("Failed to remove {}", manifest_path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn save_manifest(&self, manifest: &Manifest) -> anyhow::Result<()> {
+        if !self.root.is_dir() {
+            return Ok(());
+        }
+
+        let manifest_path = self.root.join(MANIFEST_FILE);
+        let temp_path = self.root.join(format!("{MANIFEST_FILE}.tmp"));
+        let json = serde_json::to_vec_pretty(manifest)?;
+        std::fs::write(&temp_path, json)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &manifest_path)
+            .with_context(|| format!("Failed to write {}", manifest_path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(root: &Path, encoding: EncodingConfig) -> ConversionCache {
+        ConversionCache::open(root.to_path_buf(), encoding).unwrap()
+    }
+
+    #[test]
+    fn key_changes_with_settings_and_source() {
+        let dir = std::env::temp_dir().join(format!("conversion-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let wav_path = dir.join("cry.wav");
+        std::fs::write(&wav_path, "cry").unwrap();
+
+        let default = cache(&dir, EncodingConfig::default())
+            .key(&wav_path)
+            .unwrap();
+        assert_eq!(
+            cache(&dir, EncodingConfig::default())
+                .key(&wav_path)
+                .unwrap(),
+            default
+        );
+
+        let settings = [
+            EncodingConfig {
+                quality: 0.8,
+                ..Default::default()
+            },
+            EncodingConfig {
+                sample_rate: Some(22050),
+                ..Default::default()
+            },
+            EncodingConfig {
+                backend: EncoderBackend::Ffmpeg,
+                ..Default::default()
+            },
+        ];
+        for encoding in settings {
+            assert_ne!(cache(&dir, encoding).key(&wav_path).unwrap(), default);
+        }
+
+        std::fs::write(&wav_path, "another cry").unwrap();
+        let changed = cache(&dir, EncodingConfig::default())
+            .key(&wav_path)
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(changed, default);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use pixelmon_types::species_data::{Gender, SpeciesData};
+use serde::Serialize;
+
+use crate::{atomic_file::AtomicFile, config::Config, sound_source::SoundAsset};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageStatus {
+    /// The palette plays a cry from the pack
+    Replaced,
+    /// The palette plays the cry a dumb insert species got
+    DumbInserted,
+    /// Config skips the form
+    Skipped,
+    /// No source has a cry for the form
+    Missing,
+    /// The form has a cry but something else went wrong with it
+    Failed,
+    /// The build leaves the palette alone, e.g. past the first palette with `palette_mode` first
+    Untouched,
+}
+
+/// One palette of one form, or the form itself if it has no palettes
+#[derive(Debug, Serialize)]
+pub struct CoverageEntry {
+    species: String,
+    dex: u32,
+    generation: u32,
+    form: String,
+    gender: Option<Gender>,
+    palette: Option<String>,
+    status: CoverageStatus,
+    /// Name of the sound source the cry came from
+    source: Option<String>,
+    /// How the source found it, e.g. `manual`
+    match_reason: Option<&'static str>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoverageReport {
+    /// How many entries have each status, by generation
+    generations: BTreeMap<u32, BTreeMap<CoverageStatus, usize>>,
+    entries: Vec<CoverageEntry>,
+}
+
+impl CoverageReport {
+    /// Works out coverage from species data the build has been through.
+    /// `sound_assets` is where each sound id the build registered came from, `failures` is the status of each
+    /// species and form (`None` for the whole species) that failed
+    pub(crate) fn new<'a>(
+        species: impl IntoIterator<Item = &'a SpeciesData>,
+        config: &Config,
+        sound_assets: &HashMap<String, SoundAsset>,
+        failures: &HashMap<(String, Option<String>), CoverageStatus>,
+    ) -> Self {
+        let mut entries = Vec::new();
+        for species in species {
+            let pokemon_name = species.name.to_lowercase();
+            let is_dumb = config.dumb_insert.contains(&pokemon_name);
+            let species_failure = failures.get(&(pokemon_name.clone(), None));
+
+            for (form_index, form) in species.forms.iter().enumerate() {
+                // Dumb inserts only ever touch the first form
+                let form_status = if is_dumb && form_index > 0 {
+                    Some(CoverageStatus::Untouched)
+                } else if !is_dumb && config.skips_form(&pokemon_name, &form.name) {
+                    Some(CoverageStatus::Skipped)
+                } else {
+                    species_failure
+                        .or_else(|| failures.get(&(pokemon_name.clone(), Some(form.name.clone()))))
+                        .copied()
+                };
+
+                let entry = |gender, palette, status, asset: Option<&SoundAsset>| CoverageEntry {
+                    species: pokemon_name.clone(),
+                    dex: species.dex,
+                    generation: species.generation,
+                    form: form.name.clone(),
+                    gender,
+                    palette,
+                    status,
+                    source: asset.map(|asset| asset.source.clone()),
+                    match_reason: asset.map(|asset| asset.reason.kind()),
+                    path: asset.map(|asset| asset.path.clone()),
+                };
+
+                let palettes = form
+                    .gender_properties
+                    .iter()
+                    .flatten()
+                    .flat_map(|props| {
+                        props
+                            .palettes
+                            .iter()
+                            .map(move |palette| (props.gender, palette))
+                    })
+                    .collect::<Vec<_>>();
+
+                if palettes.is_empty() {
+                    entries.push(entry(
+                        None,
+                        None,
+                        form_status.unwrap_or(CoverageStatus::Untouched),
+                        None,
+                    ));
+                    continue;
+                }
+
+                for (gender, palette) in palettes {
+                    let asset = palette
+                        .sounds
+                        .iter()
+                        .flatten()
+                        .find_map(|sound| sound_assets.get(&sound.sound_id));
+                    let status = match (form_status, asset) {
+                        (Some(status), _) => status,
+                        (None, Some(_)) if is_dumb => CoverageStatus::DumbInserted,
+                        (None, Some(_)) => CoverageStatus::Replaced,
+                        (None, None) => CoverageStatus::Untouched,
+                    };
+                    let asset = asset.filter(|_| form_status.is_none());
+                    entries.push(entry(
+                        Some(gender),
+                        Some(palette.name.clone()),
+                        status,
+                        asset,
+                    ));
+                }
+            }
+        }
+
+        entries.sort_by(|a, b| {
+            (a.dex, &a.species, &a.form, a.gender, &a.palette)
+                .cmp(&(b.dex, &b.species, &b.form, b.gender, &b.palette))
+        });
+
+        let mut generations = BTreeMap::<u32, BTreeMap<CoverageStatus, usize>>::new();
+        for entry in &entries {
+            *generations
+                .entry(entry.generation)
+                .or_default()
+                .entry(entry.status)
+                .or_default() += 1;
+        }
+
+        Self {
+            generations,
+            entries,
+        }
+    }
+
+    /// Writes the report to a temporary file, committing it moves it to `path`
+    pub fn write_json(&self, path: &Path) -> anyhow::Result<AtomicFile> {
+        let mut file = AtomicFile::create(path)?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(file)
+    }
+
+    /// Writes the report to a temporary file, committing it moves it to `path`
+    pub fn write_csv(&self, path: &Path) -> anyhow::Result<AtomicFile> {
+        let mut writer = csv::Writer::from_writer(AtomicFile::create(path)?);
+        for entry in &self.entries {
+            writer
+                .serialize(entry)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        writer
+            .into_inner()
+            .map_err(|error| error.into_error())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    process::ExitCode,
+};
+
+use pixelmon_types::species_data::{Gender, SpeciesData};
+
+use crate::{config::Config, jar::load_jar, validate::config_problems};
+
+fn species_forms(species: &[(String, SpeciesData)]) -> BTreeMap<String, BTreeSet<String>> {
+    species
+        .iter()
+        .map(|(_, species)| {
+            (
+                species.name.to_lowercase(),
+                species.forms.iter().map(|form| form.name.clone()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Sound ids of every palette, keyed by species, form, gender and palette name
+fn palette_sounds(
+    species: &[(String, SpeciesData)],
+) -> BTreeMap<(String, String, Gender, String), Vec<String>> {
+    let mut palette_sounds = BTreeMap::new();
+    for (_, species) in species {
+        for form in &species.forms {
+            for props in form.gender_properties.iter().flatten() {
+                for palette in &props.palettes {
+                    palette_sounds.insert(
+                        (
+                            species.name.to_lowercase(),
+                            form.name.clone(),
+                            props.gender,
+                            palette.name.clone(),
+                        ),
+                        palette
+                            .sounds
+                            .iter()
+                            .flatten()
+                            .map(|sound| sound.sound_id.clone())
+                            .collect(),
+                    );
+                }
+            }
+        }
+    }
+    palette_sounds
+}
+
+fn print_added_removed<'a>(
+    label: &str,
+    old: impl IntoIterator<Item = &'a String>,
+    new: impl IntoIterator<Item = &'a String>,
+) {
+    let old = old.into_iter().collect::<BTreeSet<_>>();
+    let new = new.into_iter().collect::<BTreeSet<_>>();
+    for added in new.difference(&old) {
+        println!("+ {label} {added}");
+    }
+    for removed in old.difference(&new) {
+        println!("- {label} {removed}");
+    }
+}
+
+/// Prints the species, forms, palette sounds, sound files and sounds.json keys added, removed or changed between
+/// two jars, and any entries in `config` that no longer apply to the new one
+pub(crate) fn diff(
+    old_path: &Path,
+    new_path: &Path,
+    config_path: Option<&Path>,
+) -> anyhow::Result<ExitCode> {
+    let old_jar = load_jar(old_path)?;
+    let new_jar = load_jar(new_path)?;
+
+    // Species that fail to load would otherwise just look removed
+    for (path, jar) in [(old_path, &old_jar), (new_path, &new_jar)] {
+        for (file_name, reason) in &jar.species_errors {
+            eprintln!(
+                "Failed to load {file_name} from {}: {reason:#}",
+                path.display()
+            );
+        }
+    }
+
+    let old = species_forms(&old_jar.species);
+    let new = species_forms(&new_jar.species);
+
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        println!("+ {name}");
+    }
+    for name in old.keys().filter(|name| !new.contains_key(*name)) {
+        println!("- {name}");
+    }
+
+    for (name, old_forms) in &old {
+        let Some(new_forms) = new.get(name) else {
+            continue;
+        };
+        for form in new_forms.difference(old_forms) {
+            println!("+ {name} {form}");
+        }
+        for form in old_forms.difference(new_forms) {
+            println!("- {name} {form}");
+        }
+    }
+
+    // Only palettes in both jars, the ones that come and go are covered by their forms above
+    let new_palette_sounds = palette_sounds(&new_jar.species);
+    for (key, old_sounds) in palette_sounds(&old_jar.species) {
+        let Some(new_sounds) = new_palette_sounds.get(&key) else {
+            continue;
+        };
+        if &old_sounds != new_sounds {
+            let (name, form, gender, palette) = key;
+            let sounds = |sounds: &[String]| match sounds {
+                [] => "none".to_string(),
+                sounds => sounds.join(", "),
+            };
+            println!(
+                "~ {name} {form} {gender:?} {palette}: {} -> {}",
+                sounds(&old_sounds),
+                sounds(new_sounds)
+            );
+        }
+    }
+
+    print_added_removed("sound", &old_jar.sound_files, &new_jar.sound_files);
+    print_added_removed(
+        "sounds.json",
+        old_jar.sound_registry.0.keys(),
+        new_jar.sound_registry.0.keys(),
+    );
+
+    if let Some(config_path) = config_path {
+        let config = Config::load(config_path)?;
+        let stale = config_problems(&config, &new_jar.species, &new_jar.sound_files);
+        if !stale.is_empty() {
+            println!(
+                "\n{} entries in {} no longer apply to {}:",
+                stale.len(),
+                config_path.display(),
+                new_path.display()
+            );
+            for entry in stale {
+                println!("! {entry}");
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -1,65 +1,114 @@
-use std::{collections::HashMap, path::PathBuf, sync::LazyLock};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-#[allow(clippy::type_complexity)]
-const EXPIXEL_MANUAL_MATCHES: &[((&str, Option<&str>), Option<&str>)] = &[
-    (("hooh", None), Some("ho-oh")),                 // hyphen
-    (("persian", None), Some("persain")),            // spelling
-    (("milotic", None), Some("milotic1")),           // erroneous number
-    (("whismur", None), Some("whismur1")),           // erroneous number
-    (("mimejr", None), Some("mime_jr")),             // underscore issue
-    (("brutebonnet", None), Some("brute_bonnet")),   // underscore issue
-    (("hakamo-o", None), Some("hakamoo")), // has a duplicate but the misspelled one actually sounds better lol
-    (("porygon-z", None), Some("porygonz")), // missing hyphen
-    (("irontreads", None), Some("ironthreads")), // spelling
-    (("cryogonal", None), Some("cryoganal")), // spelling
-    (("indeedee", Some("male")), Some("indeedeem")), // Form formatting
-    (("urshifu", Some("singlestrike")), None), // Has wrong file type
-    (("hoopa", Some("confined")), Some("hoopa")), // Base is confined
-    (("shaymin", Some("land")), Some("shaymin")), // Base is land
-    (("zygarde", Some("complete")), Some("zygarde-100")),
-    (("zygarde", Some("fifty_percent")), Some("zygarde-50")),
-    (("zygarde", Some("ten_percent")), Some("zygarde-10")),
-    (("calyrex", Some("icerider")), Some("calyrex-ice_rider")),
-    (
-        ("calyrex", Some("shadowrider")),
-        Some("calyrex-shadow_rider"),
-    ),
-];
+use anyhow::Context;
+use pixelmon_types::species_data::Gender;
 
-#[allow(clippy::type_complexity)]
-static MANUAL_MATCH: LazyLock<HashMap<(&'static str, Option<&'static str>), Option<&'static str>>> =
-    LazyLock::new(|| HashMap::from_iter(EXPIXEL_MANUAL_MATCHES.iter().copied()));
+use crate::sound_source::{MatchReason, SoundAsset, SoundQuery, SoundSource};
 
-pub fn get_sound_expixel(pokemon: &str, form: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
-    // Just in case base or teal form get manually passed in
-    let form = form.filter(|&some| !(some == "base" || some == "teal"));
+/// Every .ogg in the directory by file stem, read once up front rather than globbing for every lookup
+#[derive(Debug, Default)]
+pub struct ExpixelIndex {
+    files: BTreeMap<String, PathBuf>,
+}
 
-    if let Some(file_name) = MANUAL_MATCH.get(&(pokemon, form)) {
-        return Ok(file_name.map(|name| format!("expixel-sounds/{name}.ogg").into()));
+impl ExpixelIndex {
+    pub fn read(root: &Path) -> anyhow::Result<Self> {
+        let mut files = BTreeMap::new();
+        for file in root
+            .read_dir()
+            .with_context(|| format!("Failed to read {}", root.display()))?
+        {
+            let path = file?.path();
+            if path.extension().is_none_or(|extension| extension != "ogg") {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                files.insert(stem.to_string(), path);
+            }
+        }
+        Ok(Self { files })
     }
 
-    let form = form.map(|v| {
-        if v == "galarian" {
-            "galar"
-        } else if v == "hisuian" {
-            "hisui"
-        } else {
-            v
-        }
-    });
+    fn exact(&self, name: &str) -> Option<&PathBuf> {
+        self.files.get(name)
+    }
+
+    /// First file (alphabetically, like glob would give) whose name starts with `prefix`
+    fn first_matching(&self, prefix: &str) -> Option<&PathBuf> {
+        self.files
+            .range(prefix.to_string()..)
+            .next()
+            .filter(|(stem, _)| stem.starts_with(prefix))
+            .map(|(_, path)| path)
+    }
+}
 
-    let pattern = if let Some(form) = &form {
-        format!("expixel-sounds/{pokemon}-{form}*.ogg") // Sometimes a pokemon is hisui(an) or whatever so we want some fuzzy matching. Inelegant but fuck you, me.
-    } else {
-        format!("expixel-sounds/{pokemon}.ogg")
+pub fn get_sound_expixel(
+    index: &ExpixelIndex,
+    pokemon: &str,
+    form: Option<&str>,
+    gender: Option<&str>,
+) -> anyhow::Result<Option<PathBuf>> {
+    let file = match (form, gender) {
+        (Some(form), None) => index.first_matching(&format!("{pokemon}-{form}")), // Sometimes a pokemon is hisui(an) or whatever so we want some fuzzy matching. Inelegant but fuck you, me.
+        // Exact so a gendered cry never comes from some other form that happens to share the prefix
+        (Some(form), Some(gender)) => index.exact(&format!("{pokemon}-{form}-{gender}")),
+        (None, Some(gender)) => index.exact(&format!("{pokemon}-{gender}")),
+        (None, None) => index.exact(pokemon),
     };
 
-    // Todo, just read the dir once lol
-    let file = glob::glob(&pattern)?.next();
+    Ok(file.cloned())
+}
+
+/// Cries ripped from Pixelmon EX, already .ogg and mostly named `{pokemon}-{form}.ogg`
+pub struct ExpixelSource {
+    name: String,
+    index: ExpixelIndex,
+}
+
+impl ExpixelSource {
+    pub fn open(name: String, root: PathBuf) -> anyhow::Result<Self> {
+        let index = ExpixelIndex::read(&root)?;
+        Ok(Self { name, index })
+    }
+}
+
+impl SoundSource for ExpixelSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn resolve(&self, query: SoundQuery<'_>) -> anyhow::Result<Option<SoundAsset>> {
+        let expected_file_name = query.to_string();
+        Ok(get_sound_expixel(
+            &self.index,
+            query.pokemon,
+            query.form,
+            query.gender.and_then(Gender::sound_name),
+        )?
+        .map(|path| SoundAsset {
+            source: self.name.clone(),
+            reason: if path.file_stem() == Some(expected_file_name.as_ref()) {
+                MatchReason::Exact
+            } else {
+                MatchReason::Prefix
+            },
+            path,
+        }))
+    }
+
+    fn locate(&self, file: &str) -> anyhow::Result<Option<SoundAsset>> {
+        Ok(self.index.exact(file).map(|path| SoundAsset {
+            source: self.name.clone(),
+            path: path.clone(),
+            reason: MatchReason::Exact,
+        }))
+    }
 
-    match file {
-        Some(Ok(o)) => Ok(Some(o)),
-        Some(Err(e)) => Err(e.into()),
-        None => Ok(None),
+    fn read(&self, asset: &SoundAsset) -> anyhow::Result<Vec<u8>> {
+        Ok(std::fs::read(&asset.path)?)
     }
 }
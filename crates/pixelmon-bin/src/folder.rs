@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use crate::sound_source::{MatchReason, SoundAsset, SoundQuery, SoundSource};
+
+/// A plain folder of `{pokemon}-{form}.ogg` and `{pokemon}.ogg` files, no guessing involved
+pub struct FolderSource {
+    name: String,
+    root: PathBuf,
+}
+
+impl FolderSource {
+    pub fn new(name: String, root: PathBuf) -> Self {
+        Self { name, root }
+    }
+}
+
+impl SoundSource for FolderSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn resolve(&self, query: SoundQuery<'_>) -> anyhow::Result<Option<SoundAsset>> {
+        self.locate(&query.to_string())
+    }
+
+    fn locate(&self, file: &str) -> anyhow::Result<Option<SoundAsset>> {
+        let path = self.root.join(format!("{file}.ogg"));
+        Ok(path.is_file().then(|| SoundAsset {
+            source: self.name.clone(),
+            path,
+            reason: MatchReason::Exact,
+        }))
+    }
+
+    fn read(&self, asset: &SoundAsset) -> anyhow::Result<Vec<u8>> {
+        Ok(std::fs::read(&asset.path)?)
+    }
+}
@@ -0,0 +1,61 @@
+use std::{path::Path, process::ExitCode};
+
+use crate::jar::{JarContents, load_jar};
+
+/// Prints every species and form in a jar along with the sound IDs on each palette
+pub fn inspect(jar_path: &Path) -> anyhow::Result<ExitCode> {
+    let JarContents {
+        mut species,
+        species_errors,
+        sound_files,
+        ..
+    } = load_jar(jar_path)?;
+
+    species.sort_by(|(_, a), (_, b)| (a.dex, &a.name).cmp(&(b.dex, &b.name)));
+
+    for (_, species) in &species {
+        println!(
+            "#{:04} {} (generation {})",
+            species.dex, species.name, species.generation
+        );
+        for form in &species.forms {
+            println!("  {}", form.name);
+            for gender_properties in form.gender_properties.iter().flatten() {
+                for palette in &gender_properties.palettes {
+                    let sounds = palette
+                        .sounds
+                        .iter()
+                        .flatten()
+                        .map(|sound| sound.sound_id.as_str())
+                        .collect::<Vec<_>>();
+                    println!(
+                        "    {:?} {}: {}",
+                        gender_properties.gender,
+                        palette.name,
+                        if sounds.is_empty() {
+                            "no sounds".to_string()
+                        } else {
+                            sounds.join(", ")
+                        }
+                    );
+                }
+            }
+        }
+    }
+
+    println!(
+        "\n{} species, {} cry files",
+        species.len(),
+        sound_files.len()
+    );
+
+    for (file_name, reason) in &species_errors {
+        eprintln!("Failed to load {file_name}: {reason:#}");
+    }
+
+    Ok(if species_errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
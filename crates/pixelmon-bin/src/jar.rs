@@ -0,0 +1,41 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::Context;
+use pixelmon_types::{jar::PixelmonJar, sound_registry::SoundRegistry, species_data::SpeciesData};
+
+/// Everything the commands care about from a Pixelmon jar, loaded up front
+pub struct JarContents {
+    /// Path inside the jar and the species data it holds
+    pub species: Vec<(String, SpeciesData)>,
+    /// Species files that couldn't be parsed, left for the caller to decide how fatal they are
+    pub species_errors: Vec<(String, anyhow::Error)>,
+    /// File names (without directories) of the cries in the jar
+    pub sound_files: HashSet<String>,
+    /// The jar's own sounds.json
+    pub sound_registry: SoundRegistry,
+}
+
+pub fn load_jar(path: &Path) -> anyhow::Result<JarContents> {
+    let mut jar = PixelmonJar::open(path)?;
+
+    let sound_files = jar.sound_asset_names().map(String::from).collect();
+    let sound_registry = jar
+        .sound_registry()
+        .with_context(|| format!("Failed to load the sound registry from {}", path.display()))?;
+
+    let mut species = Vec::new();
+    let mut species_errors = Vec::new();
+    for (file_name, species_data) in jar.species() {
+        match species_data {
+            Ok(species_data) => species.push((file_name, species_data)),
+            Err(e) => species_errors.push((file_name, e)),
+        }
+    }
+
+    Ok(JarContents {
+        species,
+        species_errors,
+        sound_files,
+        sound_registry,
+    })
+}
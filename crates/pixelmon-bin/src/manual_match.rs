@@ -0,0 +1,187 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, anyhow, bail};
+use pixelmon_types::{sound_registry::SoundVariant, species_data::Gender};
+use serde::Deserialize;
+
+use crate::sound_source::{SoundQuery, SoundSourceChain};
+
+/// Bump this when the layout of the manual match file changes
+pub const MANUAL_MATCH_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManualMatchFile {
+    version: u32,
+    /// Source name to the overrides for that source
+    sources: HashMap<String, Vec<ManualMatchEntry>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManualMatchEntry {
+    pokemon: String,
+    #[serde(default)]
+    form: Option<String>,
+    /// Only for the cry specific to this gender
+    #[serde(default)]
+    gender: Option<Gender>,
+    #[serde(rename = "match")]
+    target: ManualMatch,
+    /// Why the entry exists, purely for the humans
+    #[serde(default)]
+    #[allow(dead_code)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ManualMatch {
+    /// Use this file (name without extension) from the source
+    File(String),
+    /// Use several takes from the source, Minecraft picks one at random each time the cry plays
+    Variants(Vec<ManualVariant>),
+    /// The source has nothing usable, skip it instead of guessing
+    Missing,
+    /// Look the pokemon up in another source instead
+    Source(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManualVariant {
+    /// File name without extension
+    pub file: String,
+    #[serde(default)]
+    pub weight: Option<u32>,
+    #[serde(default)]
+    pub volume: Option<f32>,
+    #[serde(default)]
+    pub pitch: Option<f32>,
+}
+
+impl ManualVariant {
+    pub fn settings(&self) -> SoundVariant {
+        SoundVariant {
+            weight: self.weight,
+            volume: self.volume,
+            pitch: self.pitch,
+        }
+    }
+}
+
+/// Per-source overrides for when a source's naming can't be guessed
+#[derive(Debug, Clone, Default)]
+pub struct ManualMatches(HashMap<String, HashMap<ManualMatchKey, ManualMatch>>);
+
+/// Pokemon, form and gender
+type ManualMatchKey = (String, Option<String>, Option<Gender>);
+
+impl ManualMatches {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open manual matches {}", path.display()))?;
+        let file: ManualMatchFile = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse manual matches {}", path.display()))?;
+
+        if file.version != MANUAL_MATCH_VERSION {
+            bail!(
+                "Manual matches {} is version {}, expected version {MANUAL_MATCH_VERSION}",
+                path.display(),
+                file.version
+            );
+        }
+
+        let mut matches = HashMap::new();
+        for (source, entries) in file.sources {
+            let mut source_matches = HashMap::new();
+            for entry in entries {
+                let gender = entry.gender.filter(|&gender| gender != Gender::All);
+                let key = (entry.pokemon, entry.form, gender);
+                if source_matches.insert(key.clone(), entry.target).is_some() {
+                    bail!(
+                        "Manual match for {} {:?} {:?} in source {source} is defined twice",
+                        key.0,
+                        key.1,
+                        key.2
+                    );
+                }
+            }
+            matches.insert(source, source_matches);
+        }
+
+        Ok(Self(matches))
+    }
+
+    pub fn get(&self, source: &str, query: SoundQuery<'_>) -> Option<&ManualMatch> {
+        self.0.get(source)?.get(&(
+            query.pokemon.to_string(),
+            query.form.map(String::from),
+            query.gender,
+        ))
+    }
+
+    /// Checks every source exists, every redirect lands somewhere real and every mapped file is actually there
+    pub fn validate(&self, sources: &SoundSourceChain) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+
+        for (source_name, matches) in &self.0 {
+            let Some(source) = sources.get(source_name) else {
+                problems.push(format!("unknown source {source_name}"));
+                continue;
+            };
+
+            for ((pokemon, form, gender), target) in matches {
+                let query = SoundQuery {
+                    pokemon,
+                    form: form.as_deref(),
+                    gender: *gender,
+                };
+                match target {
+                    ManualMatch::File(file) => {
+                        if source.locate(file)?.is_none() {
+                            problems.push(format!(
+                                "{source_name}: {query} maps to {file} which does not exist"
+                            ));
+                        }
+                    }
+                    ManualMatch::Variants(variants) => {
+                        if variants.is_empty() {
+                            problems.push(format!("{source_name}: {query} has no variants"));
+                        }
+                        for variant in variants {
+                            if source.locate(&variant.file)?.is_none() {
+                                problems.push(format!(
+                                    "{source_name}: {query} has variant {} which does not exist",
+                                    variant.file
+                                ));
+                            }
+                        }
+                    }
+                    ManualMatch::Missing => {}
+                    ManualMatch::Source(other) => {
+                        if other == source_name || sources.get(other).is_none() {
+                            problems.push(format!(
+                                "{source_name}: {query} redirects to invalid source {other}"
+                            ));
+                        } else if matches!(self.get(other, query), Some(ManualMatch::Source(_))) {
+                            problems.push(format!(
+                                "{source_name}: {query} redirects to {other} which redirects again"
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            problems.sort();
+            Err(anyhow!(
+                "Invalid manual matches:\n  {}",
+                problems.join("\n  ")
+            ))
+        }
+    }
+}
@@ -0,0 +1,800 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+    path::Path,
+    process::ExitCode,
+    sync::Mutex,
+};
+
+use anyhow::{Context, anyhow, bail};
+use itertools::Itertools;
+use pixelmon_types::{
+    sound_registry::{SoundRegistry, mob_sound_file_name},
+    species_data::{Form, Gender, Sound, SpeciesData},
+};
+use serde::Serialize;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    atomic_file::AtomicFile,
+    audio::process_ogg,
+    config::{Config, OutputArgs, PaletteMode, SoundRegistryMode},
+    coverage::{CoverageReport, CoverageStatus},
+    jar::{JarContents, load_jar},
+    resource_pack_writer::ResourcePackWriter,
+    sound_source::{MatchReason, ResolvedSound, SoundAsset, SoundQuery, SoundSourceChain},
+    subtitles::Subtitles,
+    validate::{config_problems, print_problems},
+};
+
+#[derive(Debug, Serialize)]
+struct PackCreationReport {
+    changed_species_files: Vec<String>,
+    added_sound_files: Vec<String>,
+    replaced_sound_files: Vec<String>,
+    unchanged_sound_files: Vec<String>,
+    /// Where each added or replaced sound file came from
+    sound_origins: BTreeMap<String, SoundAsset>,
+    /// Sound files picked by a fuzzy match with a runner up too close to call, worth pinning with a manual match
+    ambiguous_sound_files: Vec<String>,
+}
+
+/// Something that went wrong with a single species or form, collected so one missing cry doesn't sink the whole build
+#[derive(Debug)]
+struct BuildFailure {
+    species: String,
+    form: Option<String>,
+    reason: anyhow::Error,
+}
+
+/// No source has a cry, its own type so coverage can tell it apart from other failures
+#[derive(Debug)]
+struct MissingCry {
+    pokemon: String,
+    form: Option<String>,
+}
+
+impl std::fmt::Display for MissingCry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.form {
+            Some(form) => write!(f, "Failed to get sound file for {} {form}", self.pokemon),
+            None => write!(f, "Failed to get sound file for {}", self.pokemon),
+        }
+    }
+}
+
+impl std::error::Error for MissingCry {}
+
+/// Everything the worker threads share while going through the species
+struct PackContext<'a> {
+    config: &'a Config,
+    sound_sources: &'a SoundSourceChain,
+    subtitles: Option<&'a Subtitles>,
+    /// Sound files already in the jar, e.g. to find the ones under a form alias
+    existing_sound_files: &'a HashSet<String>,
+    sound_registry: Mutex<SoundRegistry>,
+    /// Where the first sound of each registered sound id came from
+    sound_assets: Mutex<HashMap<String, SoundAsset>>,
+    resource_zip: Mutex<ResourcePackWriter>,
+    failures: Mutex<Vec<BuildFailure>>,
+}
+
+fn print_failures(failures: &[BuildFailure]) {
+    let species_width = failures
+        .iter()
+        .map(|failure| failure.species.len())
+        .chain(["Species".len()])
+        .max()
+        .unwrap_or_default();
+    let form_width = failures
+        .iter()
+        .filter_map(|failure| failure.form.as_ref().map(String::len))
+        .chain(["Form".len()])
+        .max()
+        .unwrap_or_default();
+
+    eprintln!("\n{} species/forms failed:", failures.len());
+    eprintln!(
+        "{:species_width$}  {:form_width$}  Reason",
+        "Species", "Form"
+    );
+    for failure in failures {
+        eprintln!(
+            "{:species_width$}  {:form_width$}  {:#}",
+            failure.species,
+            failure.form.as_deref().unwrap_or("-"),
+            failure.reason
+        );
+    }
+}
+
+pub(crate) fn build(
+    config_path: &Path,
+    dry_run: bool,
+    output_args: &OutputArgs,
+) -> anyhow::Result<ExitCode> {
+    let config = Config::load(config_path)?;
+    let output = config.output.overridden_by(output_args);
+    let version = &config.version_number;
+    let resource_pack_path = output.path(&output.resource_pack, version)?;
+    let data_pack_path = output.path(&output.data_pack, version)?;
+    let report_path = output.path(&output.report, version)?;
+    let coverage_json_path = output.path(&format!("{}.json", output.coverage), version)?;
+    let coverage_csv_path = output.path(&format!("{}.csv", output.coverage), version)?;
+    let sound_sources = SoundSourceChain::from_config(&config)?;
+    let subtitles = config.subtitles.as_ref().map(Subtitles::load).transpose()?;
+
+    let JarContents {
+        species,
+        species_errors,
+        sound_files: existing_sound_files,
+        sound_registry: original_sound_registry,
+    } = load_jar(&config.source)?;
+
+    let problems = config_problems(&config, &species, &existing_sound_files);
+    if !problems.is_empty() {
+        print_problems(config_path, &problems);
+        return Ok(ExitCode::FAILURE);
+    }
+    for warning in species
+        .iter()
+        .filter_map(|(_, species)| config.forms.base_form_warning(species))
+    {
+        eprintln!("Warning: {warning}");
+    }
+
+    let mut failures = species_errors
+        .into_iter()
+        .map(|(file_name, reason)| BuildFailure {
+            species: file_name,
+            form: None,
+            reason,
+        })
+        .collect::<Vec<_>>();
+    let mut species_data = species
+        .into_iter()
+        .map(|(file_name, species)| (file_name, species, false))
+        .collect::<Vec<_>>();
+
+    if !dry_run && let Some(dir) = &output.dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    let resource_pack_zip = if dry_run {
+        ResourcePackWriter::dry_run()
+    } else {
+        let mut resource_pack_zip = ZipWriter::new(AtomicFile::create(&resource_pack_path)?);
+
+        resource_pack_zip
+            .add_directory_from_path(
+                "assets/pixelmon/sounds/pixelmon",
+                SimpleFileOptions::default(),
+            )
+            .context("Failed to write resource pack")?;
+
+        ResourcePackWriter::new(resource_pack_zip)
+    };
+
+    let chunk_size = species_data.len().div_ceil(config.num_threads.get()).max(1);
+
+    let ctx = PackContext {
+        config: &config,
+        sound_sources: &sound_sources,
+        subtitles: subtitles.as_ref(),
+        existing_sound_files: &existing_sound_files,
+        sound_registry: Mutex::new(SoundRegistry::default()),
+        sound_assets: Mutex::new(HashMap::new()),
+        resource_zip: Mutex::new(resource_pack_zip),
+        failures: Mutex::new(Vec::new()),
+    };
+
+    std::thread::scope(|s| {
+        for chunk in species_data.iter_mut().chunks(chunk_size).into_iter() {
+            let chunk = chunk.collect::<Vec<_>>();
+            let ctx = &ctx;
+            s.spawn(move || {
+                for (_, species, did_mutate) in chunk {
+                    process_species(species, did_mutate, ctx);
+                }
+            });
+        }
+    });
+
+    let PackContext {
+        sound_registry,
+        resource_zip,
+        sound_assets,
+        failures: species_failures,
+        ..
+    } = ctx;
+    let species_failures = species_failures.into_inner().unwrap();
+
+    let failure_statuses = species_failures
+        .iter()
+        .map(|failure| {
+            let status = if failure.reason.is::<MissingCry>() {
+                CoverageStatus::Missing
+            } else {
+                CoverageStatus::Failed
+            };
+            ((failure.species.clone(), failure.form.clone()), status)
+        })
+        .collect();
+    let coverage = CoverageReport::new(
+        species_data.iter().map(|(_, species, _)| species),
+        &config,
+        &sound_assets.into_inner().unwrap(),
+        &failure_statuses,
+    );
+    let coverage_files = if dry_run {
+        Vec::new()
+    } else {
+        vec![
+            coverage.write_json(&coverage_json_path)?,
+            coverage.write_csv(&coverage_csv_path)?,
+        ]
+    };
+
+    failures.extend(species_failures);
+
+    let (resource_pack_zip, mut added_sounds) = resource_zip.into_inner().unwrap().into_inner();
+
+    // A source that failed never made it into the pack, so there's nothing to copy
+    let (deep_copies, missing_deep_copies): (Vec<_>, Vec<_>) = config
+        .deep_copy
+        .iter()
+        .partition(|(src, _)| added_sounds.contains_key(&format!("{src}.ogg")));
+    for (src, dest) in missing_deep_copies {
+        failures.push(BuildFailure {
+            species: src.clone(),
+            form: None,
+            reason: anyhow!("Can't deep copy {src} to {dest}, {src} wasn't written"),
+        });
+    }
+    for (src, dest) in &deep_copies {
+        added_sounds.insert(
+            format!("{dest}.ogg"),
+            SoundAsset {
+                source: "deep_copy".to_string(),
+                path: format!("{src}.ogg").into(),
+                reason: MatchReason::DeepCopy,
+            },
+        );
+    }
+
+    let sound_registry = build_sound_registry(
+        sound_registry.into_inner().unwrap(),
+        &original_sound_registry,
+        config.sound_registry_mode,
+        |file_name| {
+            existing_sound_files.contains(file_name) || added_sounds.contains_key(file_name)
+        },
+    )?;
+
+    let resource_pack_file = resource_pack_zip
+        .map(|resource_pack_zip| {
+            let sound_json = serde_json::to_string_pretty(&sound_registry)?;
+            finish_resource_pack(
+                resource_pack_zip,
+                &config,
+                &deep_copies,
+                &sound_json,
+                subtitles.as_ref(),
+            )
+            .with_context(|| format!("Failed to write {}", resource_pack_path.display()))
+        })
+        .transpose()?;
+
+    let changed_species = species_data
+        .iter()
+        .filter(|(_, _, did_mutate)| *did_mutate)
+        .map(|(path, species, _)| (path.as_str(), species))
+        .collect::<Vec<_>>();
+
+    // Both packs are only moved into place once they've both been written, so a failed build leaves the old ones.
+    // Coverage follows them so it never describes packs that weren't written
+    if let Some(resource_pack_file) = resource_pack_file {
+        let data_pack_file = write_data_pack(&data_pack_path, &config, &changed_species)
+            .with_context(|| format!("Failed to write {}", data_pack_path.display()))?;
+        resource_pack_file.commit()?;
+        data_pack_file.commit()?;
+        for coverage_file in coverage_files {
+            coverage_file.commit()?;
+        }
+    }
+
+    let mut changed_species_files = changed_species
+        .iter()
+        .map(|(path, _)| path.rsplit('/').next().unwrap_or(path).to_string())
+        .collect::<Vec<_>>();
+
+    let mut added_sound_files = Vec::new();
+    let mut replaced_sound_files = Vec::new();
+    let mut unchanged_sound_files = Vec::new();
+
+    for added_sound in added_sounds.keys() {
+        if existing_sound_files.contains(added_sound) {
+            replaced_sound_files.push(added_sound.clone())
+        } else {
+            added_sound_files.push(added_sound.clone())
+        }
+    }
+
+    for existing_sound in existing_sound_files {
+        if !added_sounds.contains_key(&existing_sound) {
+            unchanged_sound_files.push(existing_sound)
+        }
+    }
+
+    changed_species_files.sort();
+    added_sound_files.sort();
+    replaced_sound_files.sort();
+    unchanged_sound_files.sort();
+    let ambiguous_sound_files = added_sounds
+        .iter()
+        .filter(|(_, asset)| asset.reason.is_ambiguous())
+        .map(|(file_name, _)| file_name.clone())
+        .collect::<Vec<_>>();
+    let pack_report = PackCreationReport {
+        changed_species_files,
+        added_sound_files,
+        replaced_sound_files,
+        unchanged_sound_files,
+        sound_origins: added_sounds,
+        ambiguous_sound_files,
+    };
+
+    if dry_run {
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &pack_report)?;
+        println!();
+    } else {
+        let mut report_file = AtomicFile::create(&report_path)?;
+        serde_json::to_writer_pretty(&mut report_file, &pack_report)
+            .with_context(|| format!("Failed to write {}", report_path.display()))?;
+        report_file.commit()?;
+    }
+
+    if failures.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        failures.sort_by(|a, b| (&a.species, &a.form).cmp(&(&b.species, &b.form)));
+        print_failures(&failures);
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Works out the sounds.json to write, every entry replaces the jar's so we don't depend on how Minecraft merges them
+fn build_sound_registry(
+    mut ours: SoundRegistry,
+    original: &SoundRegistry,
+    mode: SoundRegistryMode,
+    has_sound_file: impl Fn(&str) -> bool,
+) -> anyhow::Result<SoundRegistry> {
+    ours.inherit_subtitles(original);
+
+    let registry = match mode {
+        SoundRegistryMode::Changed => ours,
+        SoundRegistryMode::Merged => ours.merged_over(original),
+        SoundRegistryMode::Overlay => {
+            let overlay = ours.changes_from(original);
+            let mut missing = overlay
+                .sound_names()
+                .filter_map(|name| name.strip_prefix("pixelmon:pixelmon/"))
+                .filter(|name| !has_sound_file(&format!("{name}.ogg")))
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                missing.sort();
+                bail!(
+                    "sounds.json overlay points at sounds that are in neither the jar nor the pack: {}",
+                    missing.join(", ")
+                );
+            }
+            overlay
+        }
+    };
+
+    Ok(registry.replacing())
+}
+
+/// Writes everything but the sounds, committing the returned file moves the pack into place
+fn finish_resource_pack(
+    mut resource_pack_zip: ZipWriter<AtomicFile>,
+    config: &Config,
+    deep_copies: &[&(String, String)],
+    sound_json: &str,
+    subtitles: Option<&Subtitles>,
+) -> anyhow::Result<AtomicFile> {
+    resource_pack_zip
+        .start_file("assets/pixelmon/sounds.json", SimpleFileOptions::default())
+        .context("Failed to write sounds.json")?;
+    resource_pack_zip
+        .write_all(sound_json.as_bytes())
+        .context("Failed to write sounds.json")?;
+
+    for (code, entries) in subtitles.map(Subtitles::lang_files).unwrap_or_default() {
+        let lang_path = format!("assets/pixelmon/lang/{code}.json");
+        let lang_json = serde_json::to_vec_pretty(&entries)?;
+        resource_pack_zip
+            .start_file(&lang_path, SimpleFileOptions::default())
+            .with_context(|| format!("Failed to write {lang_path}"))?;
+        resource_pack_zip
+            .write_all(&lang_json)
+            .with_context(|| format!("Failed to write {lang_path}"))?;
+    }
+
+    resource_pack_zip
+        .start_file_from_path("pack.mcmeta", SimpleFileOptions::default())
+        .context("Failed to write pack.mcmeta")?;
+    resource_pack_zip
+        .write_all(config.resource_pack_mcmeta.get().as_bytes())
+        .context("Failed to write pack.mcmeta")?;
+
+    resource_pack_zip
+        .start_file_from_path("Credits.txt", SimpleFileOptions::default())
+        .context("Failed to write Credits.txt")?;
+    resource_pack_zip
+        .write_all(config.credits.as_bytes())
+        .context("Failed to write Credits.txt")?;
+
+    for (src, dest) in deep_copies {
+        resource_pack_zip
+            .deep_copy_file_from_path(
+                format!("assets/pixelmon/sounds/pixelmon/{src}.ogg"),
+                format!("assets/pixelmon/sounds/pixelmon/{dest}.ogg"),
+            )
+            .with_context(|| format!("Failed to deep copy {src} to {dest}"))?;
+    }
+
+    Ok(resource_pack_zip.finish()?)
+}
+
+/// Writes the data pack to a temporary file, committing it moves it to `path`
+fn write_data_pack(
+    path: &Path,
+    config: &Config,
+    changed_species: &[(&str, &SpeciesData)],
+) -> anyhow::Result<AtomicFile> {
+    let mut data_pack = ZipWriter::new(AtomicFile::create(path)?);
+
+    data_pack
+        .start_file("pack.mcmeta", SimpleFileOptions::default())
+        .context("Failed to write pack.mcmeta")?;
+    data_pack
+        .write_all(config.data_pack_mcmeta.get().as_bytes())
+        .context("Failed to write pack.mcmeta")?;
+
+    data_pack
+        .start_file_from_path("Credits.txt", SimpleFileOptions::default())
+        .context("Failed to write Credits.txt")?;
+    data_pack
+        .write_all(config.credits.as_bytes())
+        .context("Failed to write Credits.txt")?;
+
+    for (path, species) in changed_species {
+        let species_json = serde_json::to_vec_pretty(species)?;
+        data_pack
+            .start_file(*path, SimpleFileOptions::default())
+            .with_context(|| format!("Failed to write {path}"))?;
+        data_pack
+            .write_all(&species_json)
+            .with_context(|| format!("Failed to write {path}"))?;
+    }
+
+    Ok(data_pack.finish()?)
+}
+
+fn process_species(species: &mut SpeciesData, did_mutate: &mut bool, ctx: &PackContext) {
+    let config = ctx.config;
+    let pokemon_name = species.name.to_lowercase();
+
+    if config.dumb_insert.contains(&pokemon_name) {
+        if let Err(reason) = process_form_dumb(species, &pokemon_name, did_mutate, ctx) {
+            ctx.failures.lock().unwrap().push(BuildFailure {
+                species: pokemon_name,
+                form: None,
+                reason,
+            });
+        }
+    } else {
+        // Species names are close enough to how players see them, subtitle overrides cover the rest (e.g. HoOh)
+        let display_name = species.name.clone();
+        let inferred_base = species.inferred_base_form().map(String::from);
+        for form in species.forms.iter_mut() {
+            if config.skips_form(&pokemon_name, &form.name) {
+                continue;
+            }
+
+            let form_name = config
+                .forms
+                .canonical(&pokemon_name, inferred_base.as_deref(), &form.name)
+                .map(String::from); // form_name could be moved back into process_form to save this alloc 
+
+            if let Err(reason) = process_form(
+                form,
+                &pokemon_name,
+                &display_name,
+                form_name.as_deref(),
+                did_mutate,
+                ctx,
+            ) {
+                ctx.failures.lock().unwrap().push(BuildFailure {
+                    species: pokemon_name.clone(),
+                    form: Some(form.name.clone()),
+                    reason,
+                });
+            }
+        }
+    }
+}
+
+fn process_form(
+    form: &mut Form,
+    pokemon_name: &str,
+    display_name: &str,
+    form_name: Option<&str>,
+    did_mutate: &mut bool,
+    ctx: &PackContext,
+) -> anyhow::Result<()> {
+    let sound_files = ctx
+        .sound_sources
+        .resolve(SoundQuery::new(pokemon_name, form_name))?
+        .ok_or_else(|| MissingCry {
+            pokemon: pokemon_name.to_string(),
+            form: form_name.map(String::from),
+        })?;
+
+    let sound_id = register_cry(
+        pokemon_name,
+        display_name,
+        form_name,
+        None,
+        &sound_files,
+        ctx,
+    )?;
+
+    // Genders without a cry of their own keep the shared one
+    let mut gender_sound_ids = HashMap::new();
+    for gender in form
+        .gender_properties
+        .iter()
+        .flatten()
+        .map(|props| props.gender)
+        .filter(|&gender| gender != Gender::All)
+    {
+        if gender_sound_ids.contains_key(&gender) {
+            continue;
+        }
+        let query = SoundQuery::new(pokemon_name, form_name).with_gender(gender);
+        if let Some(sound_files) = ctx.sound_sources.resolve(query)? {
+            let gender_sound_id = register_cry(
+                pokemon_name,
+                display_name,
+                form_name,
+                Some(gender),
+                &sound_files,
+                ctx,
+            )?;
+            gender_sound_ids.insert(gender, gender_sound_id);
+        }
+    }
+
+    if assign_palette_sounds(
+        form,
+        pokemon_name,
+        display_name,
+        form_name,
+        &sound_id,
+        &gender_sound_ids,
+        ctx,
+    )? {
+        *did_mutate = true
+    }
+
+    Ok(())
+}
+
+fn process_form_dumb(
+    species: &mut SpeciesData,
+    pokemon_name: &str,
+    did_mutate: &mut bool,
+    ctx: &PackContext,
+) -> anyhow::Result<()> {
+    let sound_files = ctx
+        .sound_sources
+        .resolve(SoundQuery::new(pokemon_name, None))?
+        .ok_or_else(|| MissingCry {
+            pokemon: pokemon_name.to_string(),
+            form: None,
+        })?;
+
+    let sound_id = register_cry(pokemon_name, &species.name, None, None, &sound_files, ctx)?;
+
+    let form = species.forms.first_mut().context("no forms")?;
+
+    if assign_palette_sounds(
+        form,
+        pokemon_name,
+        &species.name,
+        None,
+        &sound_id,
+        &HashMap::new(),
+        ctx,
+    )? {
+        *did_mutate = true
+    }
+
+    Ok(())
+}
+
+/// Reads and writes a cry's sound files and registers it, returning the sound id palettes should use
+fn register_cry(
+    pokemon_name: &str,
+    display_name: &str,
+    form_name: Option<&str>,
+    gender: Option<Gender>,
+    sound_files: &[ResolvedSound],
+    ctx: &PackContext,
+) -> anyhow::Result<String> {
+    if ctx.config.fuzzy_matching.fail_on_ambiguous
+        && let Some(sound_file) = sound_files
+            .iter()
+            .find(|sound_file| sound_file.asset.reason.is_ambiguous())
+    {
+        bail!(
+            "{} is an ambiguous {}, pin it with a manual match",
+            sound_file.asset.path.display(),
+            sound_file.asset.reason
+        );
+    }
+
+    // Dry runs skip reading so nothing gets converted
+    let sound_data = if ctx.resource_zip.lock().unwrap().is_dry_run() {
+        vec![Vec::new(); sound_files.len()]
+    } else {
+        let processing = ctx.config.processing_for(pokemon_name);
+        sound_files
+            .iter()
+            .map(|sound_file| {
+                process_ogg(sound_file.read()?, &processing, &ctx.config.encoding).with_context(
+                    || format!("Failed to process {}", sound_file.asset.path.display()),
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+    let variants = sound_files
+        .iter()
+        .map(|sound_file| sound_file.variant.clone())
+        .collect::<Vec<_>>();
+
+    let subtitle = ctx
+        .subtitles
+        .map(|subtitles| subtitles.register(pokemon_name, display_name, form_name, gender));
+    let sound_id = ctx.sound_registry.lock().unwrap().register_mob_sound(
+        pokemon_name,
+        form_name,
+        gender,
+        subtitle,
+        &variants,
+    );
+    if let Some(sound_file) = sound_files.first() {
+        ctx.sound_assets
+            .lock()
+            .unwrap()
+            .insert(sound_id.clone(), sound_file.asset.clone());
+    }
+
+    // The jar's own files under a form alias get the cry too, so anything still pointing at them plays it
+    let aliases = form_name
+        .map(|form_name| ctx.config.forms.aliases(pokemon_name, form_name))
+        .unwrap_or_default();
+
+    {
+        let mut lock = ctx.resource_zip.lock().unwrap();
+        for (i, (sound_file, sound_data)) in sound_files.iter().zip(&sound_data).enumerate() {
+            lock.write_sound_file(
+                &mob_sound_file_name(pokemon_name, form_name, gender, i),
+                &sound_file.asset,
+                sound_data,
+            )?;
+
+            for &alias in &aliases {
+                let file_name = mob_sound_file_name(pokemon_name, Some(alias), gender, i);
+                if ctx
+                    .existing_sound_files
+                    .contains(&format!("{file_name}.ogg"))
+                {
+                    lock.write_sound_file(&file_name, &sound_file.asset, sound_data)?;
+                }
+            }
+        }
+    }
+
+    Ok(sound_id)
+}
+
+/// Points the form's palettes at `sound_id`, their gender's cry or their own cry if config gives them one, returns whether anything changed
+fn assign_palette_sounds(
+    form: &mut Form,
+    pokemon_name: &str,
+    display_name: &str,
+    form_name: Option<&str>,
+    sound_id: &str,
+    gender_sound_ids: &HashMap<Gender, String>,
+    ctx: &PackContext,
+) -> anyhow::Result<bool> {
+    let overrides = ctx
+        .config
+        .palette_sounds
+        .get(pokemon_name)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    // Overrides are only registered once they match a palette, then reused
+    let mut override_ids = vec![None; overrides.len()];
+
+    let gender_properties = form
+        .gender_properties
+        .as_mut()
+        .filter(|props| {
+            props
+                .first()
+                .is_some_and(|props| !props.palettes.is_empty())
+        })
+        .context(format!(
+            "No gender properties for {pokemon_name}-{}",
+            form.name
+        ))?;
+
+    let mut changed = false;
+    for (gender_index, props) in gender_properties.iter_mut().enumerate() {
+        let gender_sound_id = gender_sound_ids.get(&props.gender);
+        for (palette_index, palette) in props.palettes.iter_mut().enumerate() {
+            let palette_override = overrides
+                .iter()
+                .position(|o| o.matches(&form.name, props.gender, &palette.name));
+
+            let palette_sound_id = match palette_override {
+                Some(i) => {
+                    if override_ids[i].is_none() {
+                        let palette_sound = &overrides[i];
+                        let sound_file = ctx
+                            .sound_sources
+                            .locate(&palette_sound.sound)?
+                            .with_context(|| {
+                                format!(
+                                    "No sound source has {} for palette sounds",
+                                    palette_sound.sound
+                                )
+                            })?;
+                        override_ids[i] = Some(register_cry(
+                            pokemon_name,
+                            display_name,
+                            Some(&palette_sound.sound_form_name(form_name)),
+                            None,
+                            &[sound_file],
+                            ctx,
+                        )?);
+                    }
+                    override_ids[i].as_deref().unwrap()
+                }
+                None if ctx.config.palette_mode == PaletteMode::All
+                    || palette_index == 0 && (gender_index == 0 || gender_sound_id.is_some()) =>
+                {
+                    gender_sound_id.map(String::as_str).unwrap_or(sound_id)
+                }
+                None => continue,
+            };
+
+            let new_sounds = vec![Sound {
+                sound_id: palette_sound_id.to_string(),
+                range: 14,
+            }];
+            if palette.sounds.as_ref() != Some(&new_sounds) {
+                palette.sounds = Some(new_sounds);
+                changed = true;
+            }
+        }
+    }
+
+    Ok(changed)
+}
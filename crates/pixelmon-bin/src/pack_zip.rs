@@ -0,0 +1,68 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use zip::ZipArchive;
+
+use crate::sound_source::{MatchReason, SoundAsset, SoundQuery, SoundSource};
+
+const SOUND_DIR: &str = "assets/pixelmon/sounds/pixelmon";
+
+/// A resource pack zip made by a previous run, handy for keeping cries from sources we no longer have
+pub struct PackZipSource {
+    name: String,
+    archive: Mutex<ZipArchive<File>>,
+    sound_files: HashSet<String>,
+}
+
+impl PackZipSource {
+    pub fn open(name: String, path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let archive = ZipArchive::new(file)
+            .with_context(|| format!("Failed to read {} as a zip", path.display()))?;
+        let sound_files = archive
+            .file_names()
+            .filter(|s| s.starts_with(SOUND_DIR) && s.ends_with(".ogg"))
+            .map(String::from)
+            .collect();
+
+        Ok(Self {
+            name,
+            archive: Mutex::new(archive),
+            sound_files,
+        })
+    }
+}
+
+impl SoundSource for PackZipSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn resolve(&self, query: SoundQuery<'_>) -> anyhow::Result<Option<SoundAsset>> {
+        self.locate(&query.to_string())
+    }
+
+    fn locate(&self, file: &str) -> anyhow::Result<Option<SoundAsset>> {
+        let entry = format!("{SOUND_DIR}/{file}.ogg");
+        Ok(self.sound_files.contains(&entry).then(|| SoundAsset {
+            source: self.name.clone(),
+            path: PathBuf::from(entry),
+            reason: MatchReason::Exact,
+        }))
+    }
+
+    fn read(&self, asset: &SoundAsset) -> anyhow::Result<Vec<u8>> {
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive.by_name(&asset.path.to_string_lossy())?;
+        let mut buf = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
@@ -0,0 +1,129 @@
+use std::{path::Path, process::ExitCode};
+
+use itertools::Itertools;
+use pixelmon_types::species_data::Gender;
+
+use crate::{
+    config::Config,
+    jar::load_jar,
+    manual_match::ManualMatch,
+    sound_source::{MatchReason, SoundQuery, SoundSourceChain},
+};
+
+pub fn parse_gender(s: &str) -> Result<Gender, String> {
+    match s.to_lowercase().as_str() {
+        "all" => Ok(Gender::All),
+        "male" => Ok(Gender::Male),
+        "female" => Ok(Gender::Female),
+        _ => Err(format!("{s} is not a gender, expected male or female")),
+    }
+}
+
+/// Walks every source for a single pokemon, printing what each one would give and why
+pub fn resolve(
+    config_path: &Path,
+    species: &str,
+    form: Option<&str>,
+    gender: Option<Gender>,
+) -> anyhow::Result<ExitCode> {
+    let config = Config::load(config_path)?;
+    let sound_sources = SoundSourceChain::from_config(&config)?;
+
+    let pokemon_name = species.to_lowercase();
+
+    if let Some(form) = form
+        && config.skips_form(&pokemon_name, form)
+    {
+        println!("{pokemon_name} {form} is skipped by config, the build will leave it alone");
+    }
+
+    let form_name = if config.dumb_insert.contains(&pokemon_name) {
+        println!("{pokemon_name} is a dumb insert, forms are ignored");
+        None
+    } else {
+        // The jar decides which form is the base, same as a build
+        let jar = load_jar(&config.source)?;
+        let species_data = jar
+            .species
+            .iter()
+            .map(|(_, species)| species)
+            .find(|species| species.name.to_lowercase() == pokemon_name);
+        if species_data.is_none() {
+            println!("{pokemon_name} isn't in the jar, looking it up anyway");
+        }
+        let inferred_base = species_data.and_then(|species| species.inferred_base_form());
+        form.and_then(|form| config.forms.canonical(&pokemon_name, inferred_base, form))
+    };
+
+    let mut query = SoundQuery::new(&pokemon_name, form_name);
+    if let Some(gender) = gender {
+        query = query.with_gender(gender);
+    }
+    let names = sound_sources.names(query);
+    let query = names[0];
+    println!("Looking up {query}");
+    if names.len() > 1 {
+        println!(
+            "Falling back to {}",
+            names[1..].iter().map(ToString::to_string).join(", ")
+        );
+    }
+
+    // Same names per source as a build tries, so what's printed is what the build would do
+    for (source, name) in sound_sources.sources().flat_map(|source| {
+        sound_sources
+            .names_in(source, &names)
+            .iter()
+            .map(move |name| (source, name))
+    }) {
+        let label = if *name == query {
+            source.name().to_string()
+        } else {
+            format!("{} as {name}", source.name())
+        };
+        let resolved = sound_sources.resolve_from(source, *name, true)?;
+        match (resolved, sound_sources.manual_match(source.name(), *name)) {
+            (Some(resolved), _) => {
+                for resolved in resolved {
+                    println!(
+                        "  {label}: {} ({})",
+                        resolved.asset.path.display(),
+                        resolved.asset.reason
+                    );
+                    if let MatchReason::Fuzzy { candidates, .. } = &resolved.asset.reason {
+                        for candidate in candidates {
+                            println!("    {:.3} {}", candidate.score, candidate.name);
+                        }
+                    }
+                }
+            }
+            (None, Some(ManualMatch::Missing)) => println!("  {label}: manual match says missing"),
+            (None, Some(ManualMatch::Source(other))) => {
+                println!("  {label}: redirected to {other}, nothing found")
+            }
+            (None, _) => println!("  {label}: nothing found"),
+        }
+    }
+
+    match sound_sources.resolve(query)? {
+        Some(resolved) => {
+            for resolved in resolved {
+                println!(
+                    "Using {} from {}{}",
+                    resolved.asset.path.display(),
+                    resolved.asset.source,
+                    resolved
+                        .variant
+                        .weight
+                        .map(|weight| format!(" (weight {weight})"))
+                        .unwrap_or_default()
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        None => {
+            println!("No source has a sound for {query}");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
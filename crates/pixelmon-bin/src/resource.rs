@@ -1,156 +1,212 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
     ffi::OsStr,
     path::{Path, PathBuf},
-    sync::LazyLock,
 };
 use strsim::generic_jaro_winkler;
 
-use anyhow::{Context, anyhow};
-
-#[allow(clippy::type_complexity)]
-const RESOURCE_WAV_MANUAL_MATCHES: &[((&str, Option<&str>), Option<&str>)] = &[
-    (("pikachu", None), Some("025 - Pikachu (01)")),
-    (
-        ("ursalunabloodmoon", None),
-        Some("901B - Ursaluna (Bloodmoon)"),
-    ),
-    // Koraidon/Miraidon - only keep the main forms
-    (("koraidon", None), Some("1007AB - Koraidon (Apex Build)")),
-    (
-        ("miraidon", None),
-        Some("1008UM - Miraidon (Ultimate Mode)"),
-    ),
-    (
-        ("zacian", None),
-        Some("888H - Zacian (Hero of Many Battles)"),
-    ), // Base form
-    (
-        ("zacian", Some("crowned")),
-        Some("888C - Zacian (Crowned Sword)"),
-    ),
-    (
-        ("zamazenta", None),
-        Some("889H - Zamazenta (Hero of Many Battles)"),
-    ), // Base form
-    (
-        ("zamazenta", Some("crowned")),
-        Some("889C - Zamazenta (Crowned Shield)"),
-    ),
-    // Missing, need to pull from pokemon go or home or something
-    (("meltan", None), None),
-    (("melmetal", None), None),
-    (("floette", Some("az")), Some("670E - Floette (Eternal)")),
-    (
-        ("urshifu", Some("rapidstrike")),
-        Some("892RS - Urshifu (Rapid Strike)"),
-    ),
-    (
-        ("urshifu", Some("singlestrike")),
-        Some("892SS - Urshifu (Single Strike)"),
-    ),
-];
-
-#[allow(clippy::type_complexity)]
-static WAV_MANUAL_MATCH: LazyLock<
-    HashMap<(&'static str, Option<&'static str>), Option<&'static str>>,
-> = LazyLock::new(|| HashMap::from_iter(RESOURCE_WAV_MANUAL_MATCHES.iter().copied()));
-
-// this shit could probably use some more optimisations in future if it becomes my main source of sounds
-pub fn get_sound_resource(pokemon: &str, form: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
-    // Just in case base or teal form get manually passed in
-    let form = form.filter(|&some| !(some == "base" || some == "teal"));
-
-    let expected_file_name = form
-        .map(|form| format!("{pokemon}-{form}"))
-        .unwrap_or_else(|| pokemon.to_string());
-
-    let cached_ogg: PathBuf = format!("resource-sounds-converted/{expected_file_name}.ogg").into();
-
-    if cached_ogg.is_file() {
-        return Ok(Some(cached_ogg));
-    }
+use anyhow::Context;
 
-    let target = match WAV_MANUAL_MATCH.get(&(pokemon, form)) {
-        Some(m) => match m.map(PathBuf::from) {
-            Some(m) => {
-                let m: PathBuf = format!("resource-sounds/{}.wav", m.to_string_lossy()).into();
-                if !m.is_file() {
-                    return Err(anyhow!("File {} from manual match not found", m.display()));
-                }
-                m
-            }
-            None => return Ok(None),
-        },
-        None => {
-            let mut file_names = Vec::new();
+use pixelmon_types::species_data::Gender;
+
+use crate::{
+    config::FuzzyMatchingConfig,
+    conversion_cache::ConversionCache,
+    sound_source::{FuzzyCandidate, MatchReason, SoundAsset, SoundQuery, SoundSource},
+};
 
-            for file in PathBuf::from("resource-sounds").read_dir()? {
-                let file = file?;
+struct IndexedWav {
+    name: String,
+    /// `name` split up ahead of time since every lookup compares against it
+    chars: Vec<char>,
+    path: PathBuf,
+}
 
-                let path = file.path();
+/// Every .wav in the directory under its normalised name, read once up front rather than for every lookup
+#[derive(Default)]
+pub struct ResourceIndex {
+    wavs: Vec<IndexedWav>,
+}
 
-                if path.extension().context("no extension")? != "wav" {
-                    continue;
-                }
+impl ResourceIndex {
+    pub fn read(root: &Path, strip_from_forms: &[String]) -> anyhow::Result<Self> {
+        let mut wavs = Vec::new();
+        for path in wav_files(root)? {
+            let Some(name) = format_resource_name(&path, strip_from_forms)
+                .with_context(|| format!("Failed to index {}", path.display()))?
+            else {
+                continue;
+            };
+            wavs.push(IndexedWav {
+                chars: name.chars().collect(),
+                name,
+                path,
+            });
+        }
+        Ok(Self { wavs })
+    }
+}
 
-                let Some(name_from) = format_resource_name(&path)? else {
-                    continue;
-                };
+/// Finds the .wav for a pokemon, conversion is left until the sound is actually read
+pub(crate) fn get_sound_resource(
+    index: &ResourceIndex,
+    pokemon: &str,
+    form: Option<&str>,
+    gender: Option<&str>,
+    fuzzy_matching: &FuzzyMatchingConfig,
+) -> anyhow::Result<Option<(PathBuf, MatchReason)>> {
+    // I can't satisfy the &IntoIter requirement bullshit without collectiong to a vec
+    let expected_name = [Some(pokemon), form, gender]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("-");
+
+    // Scoring gendered names mostly matches other species sharing the -male/-female suffix, so those have to be exact
+    if gender.is_some() {
+        return Ok(index
+            .wavs
+            .iter()
+            .find(|wav| wav.name == expected_name)
+            .map(|wav| (wav.path.clone(), MatchReason::Exact)));
+    }
 
-                // I can't satisfy the &IntoIter requirement bullshit without collectiong to a vec
-                let similarity = generic_jaro_winkler(
-                    &name_from.chars().collect::<Vec<_>>(),
-                    &expected_file_name.chars().collect::<Vec<_>>(),
-                );
+    let expected_file_name = expected_name.chars().collect::<Vec<_>>();
+    let mut file_names = index
+        .wavs
+        .iter()
+        .map(|wav| {
+            (
+                generic_jaro_winkler(&wav.chars, &expected_file_name),
+                &wav.path,
+                &wav.name,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    file_names.sort_by(|(score_a, _, _), (score_b, _, _)| score_b.total_cmp(score_a));
+
+    // Near ties are things like the nidorans or the tapus, where picking either is a coin toss
+    let ambiguous = match file_names.as_slice() {
+        [(top_score, _, _), (runner_up_score, _, _), ..] => {
+            *runner_up_score >= fuzzy_matching.threshold
+                && top_score - runner_up_score <= fuzzy_matching.ambiguity_margin
+        }
+        _ => false,
+    };
 
-                file_names.push((similarity, path, name_from));
-            }
+    let candidates = file_names
+        .iter()
+        .take(fuzzy_matching.candidates)
+        .map(|(score, _, name)| FuzzyCandidate {
+            name: name.to_string(),
+            score: *score,
+        })
+        .collect::<Vec<_>>();
+
+    let Some((top_score, target, name)) = file_names.into_iter().next() else {
+        return Ok(None);
+    };
 
-            file_names.sort_by(|(score_a, _, _), (score_b, _, _)| score_b.total_cmp(score_a));
+    if top_score < fuzzy_matching.threshold {
+        return Ok(None);
+    }
 
-            let (top_score, path, _) = file_names.first().cloned().unwrap();
+    Ok(Some((
+        target.clone(),
+        MatchReason::Fuzzy {
+            score: top_score,
+            name: name.clone(),
+            candidates,
+            ambiguous,
+        },
+    )))
+}
 
-            if top_score < 0.8 {
-                return Ok(None);
-            } else {
-                path
-            }
+/// Every .wav directly in `root`
+pub fn wav_files(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut wav_files = Vec::new();
+    for file in root
+        .read_dir()
+        .with_context(|| format!("Failed to read {}", root.display()))?
+    {
+        let path = file?.path();
+        if path.extension().is_some_and(|extension| extension == "wav") {
+            wav_files.push(path);
         }
-    };
+    }
+    wav_files.sort();
+    Ok(wav_files)
+}
 
-    let converted_path = convert(&target, pokemon, form)?;
+/// Cries from the sounds resource, .wav files named `{dex} - {Name} ({Form}).wav` that get converted to .ogg on read
+pub struct ResourceSource {
+    name: String,
+    root: PathBuf,
+    index: ResourceIndex,
+    cache: ConversionCache,
+    fuzzy_matching: FuzzyMatchingConfig,
+}
 
-    Ok(Some(converted_path))
+impl ResourceSource {
+    pub(crate) fn open(
+        name: String,
+        root: PathBuf,
+        cache: ConversionCache,
+        fuzzy_matching: FuzzyMatchingConfig,
+        strip_from_forms: &[String],
+    ) -> anyhow::Result<Self> {
+        let index = ResourceIndex::read(&root, strip_from_forms)?;
+        Ok(Self {
+            name,
+            root,
+            index,
+            cache,
+            fuzzy_matching,
+        })
+    }
 }
 
-fn convert(wav_path: &Path, pokemon: &str, form: Option<&str>) -> anyhow::Result<PathBuf> {
-    let ogg_path: PathBuf = form
-        .map(|form| format!("resource-sounds-converted/{pokemon}-{form}.ogg"))
-        .unwrap_or_else(|| format!("resource-sounds-converted/{pokemon}.ogg"))
-        .into();
+impl SoundSource for ResourceSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
 
-    // belt and braces
-    if ogg_path.is_file() {
-        return Ok(ogg_path);
+    fn resolve(&self, query: SoundQuery<'_>) -> anyhow::Result<Option<SoundAsset>> {
+        Ok(get_sound_resource(
+            &self.index,
+            query.pokemon,
+            query.form,
+            query.gender.and_then(Gender::sound_name),
+            &self.fuzzy_matching,
+        )?
+        .map(|(path, reason)| SoundAsset {
+            source: self.name.clone(),
+            path,
+            reason,
+        }))
     }
 
-    let output = std::process::Command::new("ffmpeg")
-        .arg("-i")
-        .arg(wav_path)
-        .arg(&ogg_path)
-        .output()?;
+    fn locate(&self, file: &str) -> anyhow::Result<Option<SoundAsset>> {
+        let path = self.root.join(format!("{file}.wav"));
+        Ok(path.is_file().then(|| SoundAsset {
+            source: self.name.clone(),
+            path,
+            reason: MatchReason::Exact,
+        }))
+    }
 
-    if !output.status.success() {
-        Err(anyhow!("Error when converting {}", wav_path.display()))
-    } else {
-        Ok(ogg_path)
+    fn read(&self, asset: &SoundAsset) -> anyhow::Result<Vec<u8>> {
+        let ogg_path = self.cache.get_or_convert(&asset.path)?;
+        Ok(std::fs::read(ogg_path)?)
     }
 }
 
 // do what we can to get the .wav file name in-line with what we want so we can
-fn format_resource_name(path: &Path) -> anyhow::Result<Option<String>> {
+fn format_resource_name(
+    path: &Path,
+    strip_from_forms: &[String],
+) -> anyhow::Result<Option<String>> {
     let file_stem = path
         .file_stem()
         .context("No filename")
@@ -168,10 +224,12 @@ fn format_resource_name(path: &Path) -> anyhow::Result<Option<String>> {
 
     let (name_part, form_part) = file_stem.split_at(first_bracket);
 
-    let form_part = form_part
-        .trim()
-        .trim_matches([')', '('])
-        .replace("form", "") // helps cover tatsugiri and palafin
+    let form_part = strip_from_forms
+        .iter()
+        .fold(
+            form_part.trim().trim_matches([')', '(']).to_string(),
+            |form, strip| form.replace(&strip.to_lowercase(), ""),
+        )
         .trim()
         .replace(' ', "");
     let name_part = name_part.trim().replace(' ', "");
@@ -1,38 +1,55 @@
-use std::{collections::HashSet, fs::File, io::Write, path::Path};
+use std::{collections::BTreeMap, io::Write};
 
 use zip::{ZipWriter, result::ZipResult, write::SimpleFileOptions};
 
+use crate::{atomic_file::AtomicFile, sound_source::SoundAsset};
+
 pub struct ResourcePackWriter {
-    writer: ZipWriter<File>,
-    sound_list: HashSet<String>,
+    /// `None` when doing a dry run, in which case we only keep track of what would have been written
+    writer: Option<ZipWriter<AtomicFile>>,
+    /// Sound file name to where the sound came from
+    sound_list: BTreeMap<String, SoundAsset>,
 }
 
 impl ResourcePackWriter {
-    pub fn new(writer: ZipWriter<File>) -> Self {
+    pub fn new(writer: ZipWriter<AtomicFile>) -> Self {
         Self {
-            writer,
+            writer: Some(writer),
             sound_list: Default::default(),
         }
     }
 
+    pub fn dry_run() -> Self {
+        Self {
+            writer: None,
+            sound_list: Default::default(),
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.writer.is_none()
+    }
+
+    /// Writes `assets/pixelmon/sounds/pixelmon/{file_name}.ogg`, `file_name` being what [`mob_sound_file_name`](pixelmon_types::sound_registry::mob_sound_file_name) gives
     pub fn write_sound_file(
         &mut self,
-        pokemon_name: &str,
-        form_name: Option<&str>,
+        file_name: &str,
+        asset: &SoundAsset,
         sound_data: &[u8],
-        dest_path: impl AsRef<Path>,
     ) -> ZipResult<()> {
-        self.writer
-            .start_file_from_path(&dest_path, SimpleFileOptions::default())?;
-        self.writer.write_all(sound_data)?;
-        let sound_file_name: String = form_name
-            .map(|form_name| format!("{pokemon_name}-{form_name}.ogg"))
-            .unwrap_or_else(|| format!("{pokemon_name}.ogg"));
-        self.sound_list.insert(sound_file_name);
+        let sound_file_name = format!("{file_name}.ogg");
+        if let Some(writer) = &mut self.writer {
+            writer.start_file(
+                format!("assets/pixelmon/sounds/pixelmon/{sound_file_name}"),
+                SimpleFileOptions::default(),
+            )?;
+            writer.write_all(sound_data)?;
+        }
+        self.sound_list.insert(sound_file_name, asset.clone());
         Ok(())
     }
 
-    pub fn into_inner(self) -> (ZipWriter<File>, HashSet<String>) {
+    pub fn into_inner(self) -> (Option<ZipWriter<AtomicFile>>, BTreeMap<String, SoundAsset>) {
         (self.writer, self.sound_list)
     }
 }
@@ -0,0 +1,385 @@
+use std::{iter, path::PathBuf};
+
+use anyhow::Context;
+use pixelmon_types::{sound_registry::SoundVariant, species_data::Gender};
+use serde::Serialize;
+
+use crate::{
+    config::{Config, FormAliases, SoundSourceKind},
+    conversion_cache::ConversionCache,
+    expixel::ExpixelSource,
+    folder::FolderSource,
+    manual_match::{ManualMatch, ManualMatches},
+    pack_zip::PackZipSource,
+    resource::ResourceSource,
+};
+
+/// The pokemon (and form and gender) we want a cry for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundQuery<'a> {
+    pub pokemon: &'a str,
+    pub form: Option<&'a str>,
+    /// `None` for the cry every gender shares
+    pub gender: Option<Gender>,
+}
+
+impl<'a> SoundQuery<'a> {
+    pub fn new(pokemon: &'a str, form: Option<&'a str>) -> Self {
+        Self {
+            pokemon,
+            form,
+            gender: None,
+        }
+    }
+
+    /// Looks for a cry specific to `gender`, [`Gender::All`] being the shared cry
+    pub fn with_gender(self, gender: Gender) -> Self {
+        Self {
+            gender: Some(gender).filter(|&gender| gender != Gender::All),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for SoundQuery<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pokemon)?;
+        if let Some(form) = self.form {
+            write!(f, "-{form}")?;
+        }
+        if let Some(gender) = self.gender.and_then(Gender::sound_name) {
+            write!(f, "-{gender}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A sound a source has found but not necessarily read or converted yet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SoundAsset {
+    /// Name of the source that found the sound
+    pub source: String,
+    /// Where the sound lives, for sources that aren't a directory this is the path inside the source
+    pub path: PathBuf,
+    /// Why this sound was picked
+    pub reason: MatchReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchReason {
+    /// The file is named exactly what we were looking for
+    Exact,
+    /// The file name starts with what we were looking for
+    Prefix,
+    /// The closest file name by Jaro-Winkler similarity
+    Fuzzy {
+        score: f64,
+        name: String,
+        /// The best scoring names, this match included
+        candidates: Vec<FuzzyCandidate>,
+        /// The runner up scored too close to be sure this is the right file
+        ambiguous: bool,
+    },
+    /// A manual match pointed at the file
+    Manual,
+    /// A manual match sent us to this source from another one
+    Redirect { from: String },
+    /// Copied from another sound in the pack at the end of the build
+    DeepCopy,
+}
+
+impl MatchReason {
+    /// Same as the `kind` it serializes with
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MatchReason::Exact => "exact",
+            MatchReason::Prefix => "prefix",
+            MatchReason::Fuzzy { .. } => "fuzzy",
+            MatchReason::Manual => "manual",
+            MatchReason::Redirect { .. } => "redirect",
+            MatchReason::DeepCopy => "deep_copy",
+        }
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        matches!(
+            self,
+            MatchReason::Fuzzy {
+                ambiguous: true,
+                ..
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FuzzyCandidate {
+    pub name: String,
+    pub score: f64,
+}
+
+impl std::fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchReason::Exact => write!(f, "exact name match"),
+            MatchReason::Prefix => write!(f, "name prefix match"),
+            MatchReason::Fuzzy {
+                score,
+                name,
+                candidates,
+                ambiguous,
+            } => {
+                write!(f, "fuzzy match on {name:?} with score {score:.3}")?;
+                if *ambiguous {
+                    match candidates.get(1) {
+                        Some(runner_up) => write!(
+                            f,
+                            ", ambiguous with {:?} at {:.3}",
+                            runner_up.name, runner_up.score
+                        )?,
+                        None => write!(f, ", ambiguous")?,
+                    }
+                }
+                Ok(())
+            }
+            MatchReason::Manual => write!(f, "manual match"),
+            MatchReason::Redirect { from } => write!(f, "manual match redirected from {from}"),
+            MatchReason::DeepCopy => write!(f, "deep copy"),
+        }
+    }
+}
+
+pub trait SoundSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Finds the sound for a query without doing any expensive reading or converting
+    fn resolve(&self, query: SoundQuery<'_>) -> anyhow::Result<Option<SoundAsset>>;
+
+    /// Finds a file by name (without extension) for manual matches, returns `None` if it doesn't exist
+    fn locate(&self, file: &str) -> anyhow::Result<Option<SoundAsset>>;
+
+    /// Gets the .ogg data for an asset this source resolved
+    fn read(&self, asset: &SoundAsset) -> anyhow::Result<Vec<u8>>;
+}
+
+/// A sound resolved by a [`SoundSourceChain`], along with the source that can read it
+pub struct ResolvedSound<'a> {
+    pub source: &'a dyn SoundSource,
+    pub asset: SoundAsset,
+    /// How this take should be played when a cry has several
+    pub variant: SoundVariant,
+}
+
+impl ResolvedSound<'_> {
+    pub fn read(&self) -> anyhow::Result<Vec<u8>> {
+        self.source
+            .read(&self.asset)
+            .with_context(|| format!("Failed to read {}", self.asset.path.display()))
+    }
+}
+
+/// Sound sources in the order they should be tried
+pub struct SoundSourceChain {
+    sources: Vec<Box<dyn SoundSource>>,
+    manual_matches: ManualMatches,
+    forms: FormAliases,
+}
+
+impl SoundSourceChain {
+    pub(crate) fn new(
+        sources: Vec<Box<dyn SoundSource>>,
+        manual_matches: ManualMatches,
+        forms: FormAliases,
+    ) -> Self {
+        Self {
+            sources,
+            manual_matches,
+            forms,
+        }
+    }
+
+    /// Builds the chain from config (expects the sources to already be sorted by priority) and checks the manual matches against it
+    pub(crate) fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let configs = &config.sound_sources;
+        let mut sources = Vec::<Box<dyn SoundSource>>::with_capacity(configs.len());
+        for source_config in configs {
+            let name = source_config.name.clone();
+            let path = source_config.path.clone();
+            sources.push(match &source_config.kind {
+                SoundSourceKind::Expixel => Box::new(ExpixelSource::open(name, path)?),
+                SoundSourceKind::Resource {
+                    converted_path,
+                    strip_from_forms,
+                } => {
+                    let cache =
+                        ConversionCache::open(converted_path.clone(), config.encoding.clone())?;
+                    Box::new(ResourceSource::open(
+                        name,
+                        path,
+                        cache,
+                        config.fuzzy_matching.clone(),
+                        strip_from_forms,
+                    )?)
+                }
+                SoundSourceKind::Folder => Box::new(FolderSource::new(name, path)),
+                SoundSourceKind::PackZip => {
+                    Box::new(PackZipSource::open(name, &path).with_context(|| {
+                        format!("Failed to open sound source {}", source_config.name)
+                    })?)
+                }
+            });
+        }
+
+        let manual_matches = match &config.manual_matches {
+            Some(path) => ManualMatches::load(path)?,
+            None => ManualMatches::default(),
+        };
+
+        let chain = Self::new(sources, manual_matches, config.forms.clone());
+        chain.manual_matches.validate(&chain)?;
+        Ok(chain)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &dyn SoundSource> {
+        self.sources.iter().map(Box::as_ref)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn SoundSource> {
+        self.sources().find(|source| source.name() == name)
+    }
+
+    /// Every name to look a query up under, the query itself first then the form's aliases.
+    /// Queries are expected to already use the canonical form name, which needs the jar's inferred base form
+    pub fn names<'a>(&'a self, query: SoundQuery<'a>) -> Vec<SoundQuery<'a>> {
+        let aliases = query
+            .form
+            .map(|form| self.forms.aliases(query.pokemon, form))
+            .unwrap_or_default();
+        iter::once(query)
+            .chain(aliases.into_iter().map(|alias| SoundQuery {
+                form: Some(alias),
+                ..query
+            }))
+            .collect()
+    }
+
+    /// The names from [`Self::names`] worth trying in a source. A manual match has the final say for its source,
+    /// aliases are only for when there isn't one
+    pub fn names_in<'q, 'n>(
+        &self,
+        source: &dyn SoundSource,
+        names: &'n [SoundQuery<'q>],
+    ) -> &'n [SoundQuery<'q>] {
+        if self.manual_matches.get(source.name(), names[0]).is_some() {
+            &names[..1]
+        } else {
+            names
+        }
+    }
+
+    /// Returns the sounds from the first source that has any, usually just the one unless a manual match lists variants
+    pub fn resolve(&self, query: SoundQuery<'_>) -> anyhow::Result<Option<Vec<ResolvedSound<'_>>>> {
+        let names = self.names(query);
+        for source in self.sources() {
+            for &name in self.names_in(source, &names) {
+                let resolved = self
+                    .resolve_from(source, name, true)
+                    .with_context(|| format!("Failed to search sound source {}", source.name()))?;
+
+                if resolved.is_some() {
+                    return Ok(resolved);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds a file by name in the first source that has it, for sounds picked in config rather than resolved
+    pub fn locate(&self, file: &str) -> anyhow::Result<Option<ResolvedSound<'_>>> {
+        for source in self.sources() {
+            let asset = source
+                .locate(file)
+                .with_context(|| format!("Failed to search sound source {}", source.name()))?;
+
+            if let Some(asset) = asset {
+                return Ok(Some(ResolvedSound {
+                    source,
+                    asset: SoundAsset {
+                        reason: MatchReason::Manual,
+                        ..asset
+                    },
+                    variant: SoundVariant::default(),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn manual_match(&self, source: &str, query: SoundQuery<'_>) -> Option<&ManualMatch> {
+        self.manual_matches.get(source, query)
+    }
+
+    /// Resolves from a single source, following manual matches (and a single redirect if `follow_redirect`)
+    pub fn resolve_from<'a>(
+        &'a self,
+        source: &'a dyn SoundSource,
+        query: SoundQuery<'_>,
+        follow_redirect: bool,
+    ) -> anyhow::Result<Option<Vec<ResolvedSound<'a>>>> {
+        let asset = match self.manual_matches.get(source.name(), query) {
+            Some(ManualMatch::File(file)) => source.locate(file)?.map(|asset| SoundAsset {
+                reason: MatchReason::Manual,
+                ..asset
+            }),
+            Some(ManualMatch::Missing) => None,
+            Some(ManualMatch::Source(other)) if follow_redirect => {
+                let other = self
+                    .get(other)
+                    .with_context(|| format!("Manual match redirects to unknown source {other}"))?;
+                let resolved = self.resolve_from(other, query, false)?;
+                return Ok(resolved.map(|resolved| {
+                    resolved
+                        .into_iter()
+                        .map(|resolved| ResolvedSound {
+                            asset: SoundAsset {
+                                reason: MatchReason::Redirect {
+                                    from: source.name().to_string(),
+                                },
+                                ..resolved.asset
+                            },
+                            ..resolved
+                        })
+                        .collect()
+                }));
+            }
+            Some(ManualMatch::Variants(variants)) => {
+                let mut resolved = Vec::with_capacity(variants.len());
+                for variant in variants {
+                    let Some(asset) = source.locate(&variant.file)? else {
+                        return Ok(None);
+                    };
+                    resolved.push(ResolvedSound {
+                        source,
+                        asset: SoundAsset {
+                            reason: MatchReason::Manual,
+                            ..asset
+                        },
+                        variant: variant.settings(),
+                    });
+                }
+                return Ok(Some(resolved).filter(|resolved| !resolved.is_empty()));
+            }
+            Some(ManualMatch::Source(_)) => None,
+            None => source.resolve(query)?,
+        };
+
+        Ok(asset.map(|asset| {
+            vec![ResolvedSound {
+                source,
+                asset,
+                variant: SoundVariant::default(),
+            }]
+        }))
+    }
+}
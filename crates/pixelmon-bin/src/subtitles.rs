@@ -0,0 +1,147 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use pixelmon_types::species_data::Gender;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::config::SubtitleConfig;
+
+/// How to word subtitles in a single language
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SubtitleLanguage {
+    /// Subtitle for base forms, `{species}` is replaced with the species' name
+    pub template: String,
+    /// Subtitle for other forms, `{species}` and `{form}` are replaced with their names
+    pub form_template: String,
+    /// Exact subtitles for `{pokemon}`, `{pokemon}-{form}` or `{pokemon}-{form}-{gender}`, for when the templates read badly
+    #[serde(default)]
+    pub overrides: HashMap<String, String>,
+}
+
+impl SubtitleLanguage {
+    fn render(&self, lookup_name: &str, species: &str, form: Option<&str>) -> String {
+        if let Some(text) = self.overrides.get(lookup_name) {
+            return text.clone();
+        }
+
+        match form {
+            Some(form) => self
+                .form_template
+                .replace("{species}", species)
+                .replace("{form}", &display_form_name(form)),
+            None => self.template.replace("{species}", species),
+        }
+    }
+}
+
+/// Turns `fifty_percent` into `Fifty Percent`
+fn display_form_name(form: &str) -> String {
+    form.split(['_', '-'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct RegisteredSubtitle {
+    lookup_name: String,
+    species: String,
+    form: Option<String>,
+}
+
+/// Hands out subtitle keys for cries and remembers them so the lang files can be written at the end
+pub struct Subtitles {
+    /// Language code (e.g. `en_us`) to how subtitles are worded in it
+    languages: BTreeMap<String, SubtitleLanguage>,
+    registered: Mutex<BTreeMap<String, RegisteredSubtitle>>,
+}
+
+impl Subtitles {
+    pub(crate) fn load(config: &SubtitleConfig) -> anyhow::Result<Self> {
+        let mut languages = BTreeMap::new();
+        languages.insert("en_us".to_string(), config.en_us.clone());
+
+        for (code, path) in &config.extra_languages {
+            let file = std::fs::File::open(path)
+                .with_context(|| format!("Failed to open subtitle language {}", path.display()))?;
+            let language: SubtitleLanguage = serde_json::from_reader(file)
+                .with_context(|| format!("Failed to parse subtitle language {}", path.display()))?;
+            languages.insert(code.clone(), language);
+        }
+
+        Ok(Self {
+            languages,
+            registered: Default::default(),
+        })
+    }
+
+    /// Returns the subtitle key for a cry, `species` being the display name (e.g. `Bulbasaur`)
+    pub fn register(
+        &self,
+        pokemon_name: &str,
+        species: &str,
+        form_name: Option<&str>,
+        gender: Option<Gender>,
+    ) -> String {
+        // Gendered cries read like another form, e.g. "Female Indeedee cries"
+        let form_parts = form_name
+            .into_iter()
+            .chain(gender.and_then(Gender::sound_name))
+            .collect::<Vec<_>>();
+        let key = ["pixelmon.subtitle.cry", pokemon_name]
+            .into_iter()
+            .chain(form_parts.iter().copied())
+            .collect::<Vec<_>>()
+            .join(".");
+        let lookup_name = [pokemon_name]
+            .into_iter()
+            .chain(form_parts.iter().copied())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        self.registered.lock().unwrap().insert(
+            key.clone(),
+            RegisteredSubtitle {
+                lookup_name,
+                species: species.to_string(),
+                form: (!form_parts.is_empty()).then(|| form_parts.join("-")),
+            },
+        );
+        key
+    }
+
+    /// Language code to the contents of its `assets/pixelmon/lang/{code}.json`
+    pub fn lang_files(&self) -> BTreeMap<&str, BTreeMap<String, String>> {
+        let registered = self.registered.lock().unwrap();
+        self.languages
+            .iter()
+            .map(|(code, language)| {
+                let entries = registered
+                    .iter()
+                    .map(|(key, subtitle)| {
+                        (
+                            key.clone(),
+                            language.render(
+                                &subtitle.lookup_name,
+                                &subtitle.species,
+                                subtitle.form.as_deref(),
+                            ),
+                        )
+                    })
+                    .collect();
+                (code.as_str(), entries)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    process::ExitCode,
+};
+
+use itertools::Itertools;
+use pixelmon_types::{
+    sound_registry::mob_sound_file_name,
+    species_data::{Gender, SpeciesData},
+};
+use strsim::jaro_winkler;
+
+use crate::{
+    config::{Config, ConfigForm},
+    jar::load_jar,
+    sound_source::{SoundQuery, SoundSourceChain},
+};
+
+/// ", did you mean x?" for the closest candidate to a name that doesn't exist, if any are close
+fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    candidates
+        .into_iter()
+        .map(|candidate| (jaro_winkler(name, candidate), candidate))
+        .filter(|(score, _)| *score >= 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| format!(", did you mean {candidate}?"))
+        .unwrap_or_default()
+}
+
+fn species_by_name(species: &[(String, SpeciesData)]) -> HashMap<String, &SpeciesData> {
+    species
+        .iter()
+        .map(|(_, species)| (species.name.to_lowercase(), species))
+        .collect()
+}
+
+/// Names (without extension) of every sound file a build could write, so `deep_copy` knows what it can copy from,
+/// along with the cry that has to be found for it to actually be written
+fn producible_sound_files<'a>(
+    config: &'a Config,
+    species_by_name: &'a HashMap<String, &SpeciesData>,
+) -> HashMap<String, SoundQuery<'a>> {
+    let mut sound_files = HashMap::new();
+    for (pokemon, species) in species_by_name {
+        let palette_sounds = config
+            .palette_sounds
+            .get(pokemon)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        if config.dumb_insert.contains(pokemon) {
+            let query = SoundQuery::new(pokemon, None);
+            sound_files.insert(mob_sound_file_name(pokemon, None, None, 0), query);
+            for palette_sound in palette_sounds {
+                let form_name = palette_sound.sound_form_name(None);
+                sound_files.insert(
+                    mob_sound_file_name(pokemon, Some(&form_name), None, 0),
+                    query,
+                );
+            }
+            continue;
+        }
+
+        let inferred_base = species.inferred_base_form();
+        for form in &species.forms {
+            if config.skips_form(pokemon, &form.name) {
+                continue;
+            }
+            let form_name = config.forms.canonical(pokemon, inferred_base, &form.name);
+            let query = SoundQuery::new(pokemon, form_name);
+
+            sound_files.insert(mob_sound_file_name(pokemon, form_name, None, 0), query);
+            for gender in form.gender_properties.iter().flatten() {
+                if gender.gender != Gender::All {
+                    sound_files.insert(
+                        mob_sound_file_name(pokemon, form_name, Some(gender.gender), 0),
+                        query.with_gender(gender.gender),
+                    );
+                }
+            }
+            for palette_sound in palette_sounds {
+                if palette_sound
+                    .form
+                    .as_ref()
+                    .is_none_or(|palette_form| palette_form == &form.name)
+                {
+                    // Palette sounds are only handed out once the form's own cry is found
+                    let form_name = palette_sound.sound_form_name(form_name);
+                    sound_files.insert(
+                        mob_sound_file_name(pokemon, Some(&form_name), None, 0),
+                        query,
+                    );
+                }
+            }
+        }
+    }
+    sound_files
+}
+
+/// Checks every species, form and sound file config names against the jar, with suggestions for likely typos
+pub(crate) fn config_problems(
+    config: &Config,
+    species: &[(String, SpeciesData)],
+    jar_sound_files: &HashSet<String>,
+) -> Vec<String> {
+    let species_by_name = species_by_name(species);
+    let all_forms = species_by_name
+        .values()
+        .flat_map(|species| species.forms.iter().map(|form| form.name.as_str()))
+        .collect::<HashSet<_>>();
+
+    let mut problems = Vec::new();
+
+    let check_species = |field: &str, pokemon: &str, forms: &[&str]| {
+        let Some(species) = species_by_name.get(pokemon) else {
+            return vec![format!(
+                "{field} {pokemon}: no such species{}",
+                suggest(pokemon, species_by_name.keys().map(String::as_str))
+            )];
+        };
+        forms
+            .iter()
+            .sorted()
+            .filter(|form| !species.forms.iter().any(|f| f.name == **form))
+            .map(|form| {
+                format!(
+                    "{field} {pokemon}: no form {form}{}",
+                    suggest(form, species.forms.iter().map(|f| f.name.as_str()))
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for pokemon in config.dumb_insert.iter().sorted() {
+        problems.extend(check_species("dumb_insert", pokemon, &[]));
+    }
+
+    for (pokemon, skipped) in config.skip_form_names.iter().sorted_by_key(|(k, _)| *k) {
+        let forms = match skipped {
+            ConfigForm::All => Vec::new(),
+            ConfigForm::Form(forms) | ConfigForm::Except(forms) => {
+                forms.iter().map(String::as_str).collect()
+            }
+        };
+        problems.extend(check_species("skip_form_names", pokemon, &forms));
+    }
+
+    for (pokemon, aliases) in config.forms.species.iter().sorted_by_key(|(k, _)| *k) {
+        let forms = aliases
+            .base
+            .iter()
+            .flatten()
+            .chain(aliases.canonical.keys())
+            .map(String::as_str)
+            .unique()
+            .collect::<Vec<_>>();
+        problems.extend(check_species("forms", pokemon, &forms));
+
+        // Aliases are looked up by the name a form ends up with, not the jar's name for it
+        let Some(species) = species_by_name.get(pokemon) else {
+            continue;
+        };
+        let inferred_base = species.inferred_base_form();
+        let canonical_forms = species
+            .forms
+            .iter()
+            .filter_map(|form| config.forms.canonical(pokemon, inferred_base, &form.name))
+            .unique()
+            .collect::<Vec<_>>();
+        for form in aliases.aliases.keys().sorted() {
+            if !canonical_forms.contains(&form.as_str()) {
+                problems.push(format!(
+                    "forms {pokemon}: no form is named {form} to alias{}",
+                    suggest(form, canonical_forms.iter().copied())
+                ));
+            }
+        }
+    }
+
+    for (pokemon, palette_sounds) in config.palette_sounds.iter().sorted_by_key(|(k, _)| *k) {
+        let forms = palette_sounds
+            .iter()
+            .filter_map(|palette_sound| palette_sound.form.as_deref())
+            .unique()
+            .collect::<Vec<_>>();
+        problems.extend(check_species("palette_sounds", pokemon, &forms));
+    }
+
+    for pokemon in config.species_processing.keys().sorted() {
+        problems.extend(check_species("species_processing", pokemon, &[]));
+    }
+
+    for form in config.skip_form_names_all.iter().sorted() {
+        if !all_forms.contains(form.as_str()) {
+            problems.push(format!(
+                "skip_form_names_all {form}: no species has this form{}",
+                suggest(form, all_forms.iter().copied())
+            ));
+        }
+    }
+
+    let producible = producible_sound_files(config, &species_by_name);
+    for (src, dest) in &config.deep_copy {
+        if !producible.contains_key(src) {
+            problems.push(format!(
+                "deep_copy {src} -> {dest}: {src} is not a sound the build writes{}",
+                suggest(src, producible.keys().map(String::as_str))
+            ));
+        }
+        if !jar_sound_files.contains(&format!("{dest}.ogg")) {
+            problems.push(format!(
+                "deep_copy {src} -> {dest}: {dest} is not a sound file in the jar{}",
+                suggest(
+                    dest,
+                    jar_sound_files
+                        .iter()
+                        .filter_map(|file| file.strip_suffix(".ogg"))
+                )
+            ));
+        }
+    }
+
+    problems
+}
+
+pub(crate) fn print_problems(config_path: &Path, problems: &[String]) {
+    eprintln!("{} has {} problems:", config_path.display(), problems.len());
+    for problem in problems {
+        eprintln!("  {problem}");
+    }
+}
+
+/// Loads a config and everything it points at, reporting problems without writing anything
+pub fn validate(config_path: &Path) -> anyhow::Result<ExitCode> {
+    let config = Config::load(config_path)?;
+    // Building the chain checks the manual matches
+    let sound_sources = SoundSourceChain::from_config(&config)?;
+
+    let jar = load_jar(&config.source)?;
+
+    // Config is allowed to disagree with the jar on purpose, so these don't fail validation
+    for warning in jar
+        .species
+        .iter()
+        .filter_map(|(_, species)| config.forms.base_form_warning(species))
+    {
+        eprintln!("Warning: {warning}");
+    }
+
+    let mut problems = Vec::new();
+    for (file_name, reason) in &jar.species_errors {
+        problems.push(format!("failed to load {file_name}: {reason:#}"));
+    }
+    problems.extend(config_problems(&config, &jar.species, &jar.sound_files));
+
+    // Build can only copy what it wrote, which depends on the sound sources rather than just the jar
+    let species_by_name = species_by_name(&jar.species);
+    let producible = producible_sound_files(&config, &species_by_name);
+    for (src, dest) in &config.deep_copy {
+        if let Some(&query) = producible.get(src)
+            && sound_sources.resolve(query)?.is_none()
+        {
+            problems.push(format!(
+                "deep_copy {src} -> {dest}: no sound source has the cry for {src}"
+            ));
+        }
+    }
+
+    for (pokemon, palette_sounds) in &config.palette_sounds {
+        for palette_sound in palette_sounds {
+            if sound_sources.locate(&palette_sound.sound)?.is_none() {
+                problems.push(format!(
+                    "palette sound {} for {pokemon} is not in any sound source",
+                    palette_sound.sound
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        println!("{} is valid", config_path.display());
+        Ok(ExitCode::SUCCESS)
+    } else {
+        print_problems(config_path, &problems);
+        Ok(ExitCode::FAILURE)
+    }
+}
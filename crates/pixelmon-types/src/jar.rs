@@ -0,0 +1,142 @@
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+};
+
+use anyhow::Context;
+use zip::ZipArchive;
+
+use crate::{sound_registry::SoundRegistry, species_data::SpeciesData};
+
+pub const SOUNDS_JSON_PATH: &str = "assets/pixelmon/sounds.json";
+pub const SOUND_DIR: &str = "assets/pixelmon/sounds/pixelmon/";
+
+/// Whether a path in the jar is a species file (skipping missingno, who isn't real)
+pub fn is_pixelmon_filepath(s: &str) -> bool {
+    let mut splits = s.split('/').rev();
+    let Some(json_question_mark) = splits.next() else {
+        return false;
+    };
+    let Some(is_species_question_mark) = splits.next() else {
+        return false;
+    };
+
+    is_species_question_mark == "species"
+        && json_question_mark.ends_with(".json")
+        && !json_question_mark.contains("000_missingno")
+}
+
+/// Whether a path in the jar is one of the pokemon sounds
+pub fn is_pixelmon_sound_file(s: &str) -> bool {
+    s.starts_with(SOUND_DIR) && s.ends_with(".ogg")
+}
+
+/// Read access to the data we care about in a Pixelmon jar
+pub struct PixelmonJar<R = File> {
+    archive: ZipArchive<R>,
+    species_paths: Vec<String>,
+}
+
+impl PixelmonJar<File> {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Self::new(file).with_context(|| format!("Failed to read {} as a jar", path.display()))
+    }
+}
+
+impl<R: Read + Seek> PixelmonJar<R> {
+    pub fn new(reader: R) -> anyhow::Result<Self> {
+        let archive = ZipArchive::new(reader)?;
+        let mut species_paths = archive
+            .file_names()
+            .filter(|s| is_pixelmon_filepath(s))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        species_paths.sort();
+
+        Ok(Self {
+            archive,
+            species_paths,
+        })
+    }
+
+    /// Paths of every species file, sorted
+    pub fn species_paths(&self) -> &[String] {
+        &self.species_paths
+    }
+
+    /// Parses each species file in turn, errors are per file so one bad species doesn't stop the rest
+    pub fn species(&mut self) -> SpeciesIter<'_, R> {
+        SpeciesIter {
+            archive: &mut self.archive,
+            paths: self.species_paths.iter(),
+        }
+    }
+
+    pub fn sound_registry(&mut self) -> anyhow::Result<SoundRegistry> {
+        let bytes = self.asset_bytes(SOUNDS_JSON_PATH)?;
+        let de = &mut serde_json::Deserializer::from_slice(&bytes);
+        serde_path_to_error::deserialize(de)
+            .with_context(|| format!("Failed to parse {SOUNDS_JSON_PATH}"))
+    }
+
+    /// Full paths of every pokemon sound file
+    pub fn sound_asset_paths(&self) -> impl Iterator<Item = &str> {
+        self.archive
+            .file_names()
+            .filter(|s| is_pixelmon_sound_file(s))
+    }
+
+    /// File names (without directories) of every pokemon sound file
+    pub fn sound_asset_names(&self) -> impl Iterator<Item = &str> {
+        self.sound_asset_paths()
+            .filter_map(|s| s.rsplit('/').next())
+    }
+
+    pub fn asset_bytes(&mut self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let mut file = self
+            .archive
+            .by_name(path)
+            .with_context(|| format!("Failed to find {path} in jar"))?;
+        let mut buf = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut buf)
+            .with_context(|| format!("Failed to read {path} from jar"))?;
+        Ok(buf)
+    }
+}
+
+pub struct SpeciesIter<'a, R> {
+    archive: &'a mut ZipArchive<R>,
+    paths: std::slice::Iter<'a, String>,
+}
+
+impl<R: Read + Seek> Iterator for SpeciesIter<'_, R> {
+    /// Path of the species file and its data
+    type Item = (String, anyhow::Result<SpeciesData>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.paths.next()?;
+        let species = read_species(self.archive, path)
+            .with_context(|| format!("Failed to load species data from {path}"));
+
+        Some((path.clone(), species))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.paths.size_hint()
+    }
+}
+
+fn read_species<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+) -> anyhow::Result<SpeciesData> {
+    let mut file = archive.by_name(path)?;
+    let mut buf = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut buf)?;
+    let de = &mut serde_json::Deserializer::from_slice(&buf);
+    Ok(serde_path_to_error::deserialize(de)?)
+}
@@ -1,2 +1,3 @@
+pub mod jar;
 pub mod sound_registry;
 pub mod species_data;
@@ -2,54 +2,230 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+use crate::species_data::Gender;
+
+/// Name (without extension) of a cry's sound file, `{mob}-{form}-{gender}` for the first variant and the rest get `_v2`, `_v3`...
+pub fn mob_sound_file_name(
+    mob_name: &str,
+    form_name: Option<&str>,
+    gender: Option<Gender>,
+    variant: usize,
+) -> String {
+    let base = [
+        Some(mob_name),
+        form_name,
+        gender.and_then(Gender::sound_name),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join("-");
+    match variant {
+        0 => base,
+        n => format!("{base}_v{}", n + 1),
+    }
+}
+
+/// How one take of a cry should be played, `None` leaves it to Minecraft's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SoundVariant {
+    /// Relative chance of this take being picked
+    pub weight: Option<u32>,
+    pub volume: Option<f32>,
+    pub pitch: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct SoundRegistry(pub HashMap<String, SoundInfo>);
 
 impl SoundRegistry {
-    pub fn register_mob_sound(&mut self, mob_name: &str, form_name: Option<&str>) -> String {
-        let (k, file_name) = if let Some(form_name) = form_name {
-            (
-                format!("pixelmon.mob.{mob_name}.{form_name}"),
-                format!("pixelmon:pixelmon/{mob_name}-{form_name}"),
-            )
-        } else {
-            (
-                format!("pixelmon.mob.{mob_name}"),
-                format!("pixelmon:pixelmon/{mob_name}"),
-            )
-        };
+    /// Registers a cry with one sound per variant (named by [`mob_sound_file_name`]), returns the sound id to put in palettes
+    pub fn register_mob_sound(
+        &mut self,
+        mob_name: &str,
+        form_name: Option<&str>,
+        gender: Option<Gender>,
+        subtitle: Option<String>,
+        variants: &[SoundVariant],
+    ) -> String {
+        let k = ["pixelmon.mob", mob_name]
+            .into_iter()
+            .chain(form_name)
+            .chain(gender.and_then(Gender::sound_name))
+            .collect::<Vec<_>>()
+            .join(".");
 
         let v = SoundInfo {
-            sounds: SoundList::Stream(vec![SoundListItem {
-                name: file_name,
-                stream: false,
-            }]),
-            subtitle: None, // None of my cries are subtitled
+            sounds: SoundList(
+                variants
+                    .iter()
+                    .enumerate()
+                    .map(|(i, variant)| {
+                        SoundListEntry::Item(SoundListItem {
+                            weight: variant.weight,
+                            volume: variant.volume,
+                            pitch: variant.pitch,
+                            ..SoundListItem::new(format!(
+                                "pixelmon:pixelmon/{}",
+                                mob_sound_file_name(mob_name, form_name, gender, i)
+                            ))
+                        })
+                    })
+                    .collect(),
+            ),
+            subtitle,
+            replace: None,
         };
 
         self.0.insert(k.clone(), v);
         format!("pixelmon:{k}")
     }
+
+    /// Fills in subtitles we don't have from `base`, so overriding an entry doesn't lose its subtitle
+    pub fn inherit_subtitles(&mut self, base: &SoundRegistry) {
+        for (k, v) in self.0.iter_mut() {
+            if v.subtitle.is_none() {
+                v.subtitle = base.0.get(k).and_then(|base| base.subtitle.clone());
+            }
+        }
+    }
+
+    /// `base` with every entry of ours layered on top
+    pub fn merged_over(&self, base: &SoundRegistry) -> SoundRegistry {
+        let mut merged = base.clone();
+        merged
+            .0
+            .extend(self.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+        merged
+    }
+
+    /// Only our entries that aren't already identical in `base`
+    pub fn changes_from(&self, base: &SoundRegistry) -> SoundRegistry {
+        SoundRegistry(
+            self.0
+                .iter()
+                .filter(|(k, v)| base.0.get(*k) != Some(*v))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        )
+    }
+
+    /// Marks every entry as replacing whatever lower packs define for the same key
+    pub fn replacing(mut self) -> SoundRegistry {
+        for v in self.0.values_mut() {
+            v.replace = Some(true);
+        }
+        self
+    }
+
+    /// Every sound name referenced by the registry, e.g. `pixelmon:pixelmon/bulbasaur`
+    pub fn sound_names(&self) -> impl Iterator<Item = &str> {
+        self.0.values().flat_map(|v| v.sounds.names())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct SoundInfo {
     pub sounds: SoundList,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subtitle: Option<String>,
+    /// Whether this entry replaces the sounds lower resource packs define for the same key instead of adding to them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged, deny_unknown_fields)]
-pub enum SoundList {
-    Raw(Vec<String>),
-    Stream(Vec<SoundListItem>),
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct SoundList(pub Vec<SoundListEntry>);
+
+impl SoundList {
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(SoundListEntry::name)
+    }
+}
+
+/// Vanilla lets a list mix plain names with objects, so each entry is either
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum SoundListEntry {
+    Name(String),
+    Item(SoundListItem),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl SoundListEntry {
+    pub fn name(&self) -> &str {
+        match self {
+            SoundListEntry::Name(name) => name,
+            SoundListEntry::Item(item) => &item.name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct SoundListItem {
     pub name: String,
+    #[serde(default)]
     pub stream: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preload: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attenuation_distance: Option<u32>,
+    /// Whether `name` is a sound file or another sound event
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SoundListItemKind>,
+}
+
+impl SoundListItem {
+    /// A non-streamed sound file with everything else left to Minecraft's defaults
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            stream: false,
+            weight: None,
+            volume: None,
+            pitch: None,
+            preload: None,
+            attenuation_distance: None,
+            kind: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundListItemKind {
+    File,
+    Event,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sound_list_mixes_names_and_objects() {
+        let info: SoundInfo = serde_json::from_str(
+            r#"{ "sounds": ["pixelmon:a", { "name": "pixelmon:b", "weight": 2 }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            info.sounds.names().collect::<Vec<_>>(),
+            ["pixelmon:a", "pixelmon:b"]
+        );
+        assert_eq!(
+            info.sounds.0[1],
+            SoundListEntry::Item(SoundListItem {
+                weight: Some(2),
+                ..SoundListItem::new("pixelmon:b".to_string())
+            })
+        );
+    }
 }
@@ -12,6 +12,25 @@ pub struct SpeciesData {
     pub generation: u32,
 }
 
+impl SpeciesData {
+    /// The form the species is by default, from `defaultForms` or failing that the `defaultBaseForm` every form agrees on.
+    /// `None` when the data lists several or none at all
+    pub fn inferred_base_form(&self) -> Option<&str> {
+        match self.default_forms.as_slice() {
+            [form] => Some(form),
+            [] => {
+                let mut base_forms = self
+                    .forms
+                    .iter()
+                    .filter_map(|form| form.default_base_form.as_deref());
+                let first = base_forms.next()?;
+                base_forms.all(|form| form == first).then_some(first)
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct Form {
@@ -297,13 +316,32 @@ pub enum SpawnLocation {
 
 // Most controversial enum I will ever write
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "schemars",
+    schemars(extend("enum" = ["ALL", "MALE", "FEMALE", "all", "male", "female"]))
+)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Gender {
+    #[serde(alias = "all")]
     All,
+    #[serde(alias = "male")]
     Male,
+    #[serde(alias = "female")]
     Female,
 }
 
+impl Gender {
+    /// How the gender is written in sound names, `None` for [`Gender::All`] since that's just the normal cry
+    pub fn sound_name(self) -> Option<&'static str> {
+        match self {
+            Gender::All => None,
+            Gender::Male => Some("male"),
+            Gender::Female => Some("female"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct GenderProperties {